@@ -0,0 +1,50 @@
+//! Compares the sequential and parallel breadth-first searches on a large
+//! synthetic maze. Run with `cargo bench --bench bfs`.
+
+use advent::bfs::{par_layers, Bfs};
+use advent::pos::{Dir, Pos};
+use std::time::{Duration, Instant};
+
+const SIZE: i64 = 2000;
+const RUNS: u32 = 3;
+
+/// A square grid of 3x3 rooms separated by walls. Every wall between two
+/// rooms has a single door at a pseudo-random offset.
+fn open(p: Pos) -> bool {
+    let in_bounds = 0 <= p.x && p.x < SIZE && 0 <= p.y && p.y < SIZE;
+    let door = |wall: i64, room: i64| 4 * room + (wall * 7919 + room * 104729) % 3;
+    let blocked = |along: i64, across: i64| along % 4 == 3 && (across % 4 == 3 || across != door(along, across / 4));
+    in_bounds && !blocked(p.x, p.y) && !blocked(p.y, p.x)
+}
+
+fn moves(&p: &Pos) -> Vec<Pos> {
+    Dir::ELEMS
+        .iter()
+        .map(|&d| p.advance(d, 1))
+        .filter(|&q| open(q))
+        .collect()
+}
+
+fn time<T>(name: &str, mut f: impl FnMut() -> T) -> T {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = Some(f());
+        best = best.min(start.elapsed());
+    }
+    println!("{:>12}: {:?}", name, best);
+    result.unwrap()
+}
+
+fn main() {
+    let sequential = time("Bfs", || Bfs::new(Pos::ORIGIN, moves, |&p| p).with_depths().count());
+    let parallel = time("par_layers", || {
+        par_layers(vec![Pos::ORIGIN], moves, |&p| p)
+            .iter()
+            .map(Vec::len)
+            .sum::<usize>()
+    });
+    assert_eq!(sequential, parallel);
+    println!("{} states reached", sequential);
+}
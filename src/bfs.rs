@@ -1,52 +1,472 @@
-use std::hash::Hash;
-use std::collections::{HashSet, VecDeque};
+use std::hash::{BuildHasher, Hash};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::{Entry, RandomState};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// How a visited state was first reached
+#[derive(Clone)]
+struct Node<C> {
+    parent: Option<C>,
+    depth: usize,
+}
 
 #[derive(Clone)]
 pub struct Bfs<S, C, TFn, CFn> {
-    queue: VecDeque<S>,
-    visited: HashSet<C>,
+    queue: VecDeque<(S, Option<C>, usize)>,
+    visited: HashMap<C, Node<C>>,
     transition: TFn,
     characterize: CFn,
+    max_depth: Option<usize>,
 }
 
-impl<S, C, TFn, CFn> Bfs<S, C, TFn, CFn>
+impl<S, C, I, TFn, CFn> Bfs<S, C, TFn, CFn>
     where
-        S: Clone,
-        C: Eq + Hash,
-        TFn: Fn(&S) -> Vec<S>,
-        CFn: Fn(&S) -> C,
+        C: Clone + Eq + Hash,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = S>,
+        CFn: FnMut(&S) -> C,
 {
     pub fn new(
         initial: S,
         transition: TFn,
         characterize: CFn,
     ) -> Bfs<S, C, TFn, CFn>
+    {
+        Bfs::from_sources(vec![initial], transition, characterize)
+    }
+
+    /// Search starting from every one of the given states at depth 0
+    pub fn from_sources<J: IntoIterator<Item = S>>(
+        initials: J,
+        transition: TFn,
+        characterize: CFn,
+    ) -> Bfs<S, C, TFn, CFn>
     {
         Bfs {
-            queue: VecDeque::from(vec![initial]),
-            visited: HashSet::new(),
+            queue: initials.into_iter().map(|s| (s, None, 0)).collect(),
+            visited: HashMap::new(),
             transition,
             characterize,
+            max_depth: None,
         }
     }
+
+    /// Stop expanding states once they are `depth` transitions away from
+    /// the initial states. States at exactly that depth are still yielded.
+    pub fn max_depth(mut self, depth: usize) -> Bfs<S, C, TFn, CFn> {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Yield each state paired with its depth
+    pub fn with_depths(mut self) -> impl Iterator<Item = (usize, S)> {
+        std::iter::from_fn(move || self.next_with_depth())
+    }
+
+    /// Yield all of the states at each depth together, in order of depth
+    pub fn layers(self) -> Layers<S, C, TFn, CFn> {
+        Layers { bfs: self, pending: None }
+    }
+
+    fn next_with_depth(&mut self) -> Option<(usize, S)> {
+        while let Some((s, parent, depth)) = self.queue.pop_front() {
+            let c = (self.characterize)(&s);
+            if let Entry::Vacant(entry) = self.visited.entry(c.clone()) {
+                entry.insert(Node { parent, depth });
+                if self.max_depth.is_none_or(|max| depth < max) {
+                    self.queue.extend((self.transition)(&s).into_iter().map(|n| (n, Some(c.clone()), depth + 1)));
+                }
+                return Some((depth, s));
+            }
+        }
+        None
+    }
+
+    /// Number of transitions from the initial state to a yielded state
+    pub fn depth(&self, c: &C) -> Option<usize> {
+        self.visited.get(c).map(|n| n.depth)
+    }
+
+    /// Characterizations of the states along a shortest path from the
+    /// initial state to a yielded state, inclusive. Empty when the state
+    /// hasn't been yielded. Only parent links are kept, not the states
+    /// themselves, so the path can't be given as states.
+    pub fn keys_to(&self, c: &C) -> Vec<C> {
+        let mut path = walk_back(&self.visited, c);
+        path.reverse();
+        path
+    }
+
+    /// Characterizations of all states yielded so far
+    pub fn visited(&self) -> impl Iterator<Item = &C> {
+        self.visited.keys()
+    }
+}
+
+/// Characterizations from the given one back to its search root by
+/// parent links
+fn walk_back<C: Clone + Eq + Hash>(visited: &HashMap<C, Node<C>>, c: &C) -> Vec<C> {
+    let mut path = vec![];
+    let mut next = visited.get_key_value(c);
+    while let Some((c, node)) = next {
+        path.push(c.clone());
+        next = node.parent.as_ref().and_then(|p| visited.get_key_value(p));
+    }
+    path
 }
 
-impl<S, C, TFn, CFn> Iterator for Bfs<S, C, TFn, CFn>
+/// One direction of a bidirectional search
+struct Side<S, C> {
+    visited: HashMap<C, Node<C>>,
+    /// States found in the latest layer, which are the only ones kept
+    frontier: Vec<(C, S)>,
+    layers: usize,
+}
+
+impl<S, C: Clone + Eq + Hash> Side<S, C> {
+    fn new<J, CFn>(initials: J, characterize: &mut CFn) -> Side<S, C>
+    where
+        J: IntoIterator<Item = S>,
+        CFn: FnMut(&S) -> C,
+    {
+        let mut side = Side { visited: HashMap::new(), frontier: vec![], layers: 0 };
+        for state in initials {
+            let c = characterize(&state);
+            if let Entry::Vacant(entry) = side.visited.entry(c.clone()) {
+                entry.insert(Node { parent: None, depth: 0 });
+                side.frontier.push((c, state));
+            }
+        }
+        side
+    }
+
+    /// Expand the whole frontier by one layer, returning the newly found
+    /// state on the shortest combined path to the other side, if any.
+    fn expand<I, TFn, CFn>(&mut self, other: &Side<S, C>, transition: &mut TFn, characterize: &mut CFn) -> Option<C>
+    where
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = S>,
+        CFn: FnMut(&S) -> C,
+    {
+        let mut best: Option<(usize, C)> = None;
+        self.layers += 1;
+        for (c, state) in std::mem::take(&mut self.frontier) {
+            let depth = self.visited[&c].depth + 1;
+            for next in transition(&state) {
+                let nc = characterize(&next);
+                if let Entry::Vacant(entry) = self.visited.entry(nc.clone()) {
+                    entry.insert(Node { parent: Some(c.clone()), depth });
+                    if let Some(node) = other.visited.get(&nc) {
+                        if best.as_ref().is_none_or(|&(d, _)| depth + node.depth < d) {
+                            best = Some((depth + node.depth, nc.clone()));
+                        }
+                    }
+                    self.frontier.push((nc, next));
+                }
+            }
+        }
+        best.map(|(_, c)| c)
+    }
+}
+
+/// Find a shortest path from any start state to any goal state by
+/// searching forward from the starts and backward from the goals, a layer
+/// at a time from whichever side has the smaller frontier (or is shallower
+/// on ties), until the two searches meet. `backward` must produce the
+/// predecessors of a state; for undirected graphs it is the same function
+/// as `forward`. The path is given as the characterizations of its states.
+///
+/// The goals are explicit states rather than a predicate, since the
+/// backward search has to start from them. Goals that can only be
+/// recognized by a predicate need a one-sided `Bfs` and `Iterator::find`.
+pub fn bidirectional<S, C, I, J, FFn, BFn, CFn, SI, GI>(
+    starts: SI,
+    goals: GI,
+    mut forward: FFn,
+    mut backward: BFn,
+    mut characterize: CFn,
+) -> Option<Vec<C>>
+    where
+        C: Clone + Eq + Hash,
+        FFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = S>,
+        BFn: FnMut(&S) -> J,
+        J: IntoIterator<Item = S>,
+        CFn: FnMut(&S) -> C,
+        SI: IntoIterator<Item = S>,
+        GI: IntoIterator<Item = S>,
+{
+    let mut fwd = Side::new(starts, &mut characterize);
+    let mut bwd = Side::new(goals, &mut characterize);
+
+    let mut meet = fwd.frontier.iter().map(|(c, _)| c).find(|c| bwd.visited.contains_key(c)).cloned();
+    while meet.is_none() && !fwd.frontier.is_empty() && !bwd.frontier.is_empty() {
+        let fwd_size = (fwd.frontier.len(), fwd.layers);
+        let bwd_size = (bwd.frontier.len(), bwd.layers);
+        meet = if fwd_size <= bwd_size {
+            fwd.expand(&bwd, &mut forward, &mut characterize)
+        } else {
+            bwd.expand(&fwd, &mut backward, &mut characterize)
+        };
+    }
+
+    let meet = meet?;
+    let mut path = walk_back(&fwd.visited, &meet);
+    path.reverse();
+    path.extend(walk_back(&bwd.visited, &meet).into_iter().skip(1));
+    Some(path)
+}
+
+impl<S, C, I, TFn, CFn> Iterator for Bfs<S, C, TFn, CFn>
     where
-        S: Clone,
-        C: Eq + Hash,
-        TFn: Fn(&S) -> Vec<S>,
-        CFn: Fn(&S) -> C,
+        C: Clone + Eq + Hash,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = S>,
+        CFn: FnMut(&S) -> C,
 {
     type Item = S;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(s) = self.queue.pop_front() {
-            if self.visited.insert((self.characterize)(&s)) {
-                self.queue.extend((self.transition)(&s));
-                return Some(s);
+        self.next_with_depth().map(|(_, s)| s)
+    }
+}
+
+/// Breadth-first search yielding one frontier per depth
+pub struct Layers<S, C, TFn, CFn> {
+    bfs: Bfs<S, C, TFn, CFn>,
+    pending: Option<(usize, S)>,
+}
+
+impl<S, C, TFn, CFn> Layers<S, C, TFn, CFn> {
+    /// The underlying search, for looking up paths and depths
+    pub fn bfs(&self) -> &Bfs<S, C, TFn, CFn> {
+        &self.bfs
+    }
+}
+
+impl<S, C, I, TFn, CFn> Iterator for Layers<S, C, TFn, CFn>
+    where
+        C: Clone + Eq + Hash,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = S>,
+        CFn: FnMut(&S) -> C,
+{
+    type Item = Vec<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, first) = self.pending.take().or_else(|| self.bfs.next_with_depth())?;
+        let mut layer = vec![first];
+        while let Some((d, s)) = self.bfs.next_with_depth() {
+            if d == depth {
+                layer.push(s)
+            } else {
+                self.pending = Some((d, s));
+                break;
             }
         }
-        None
+        Some(layer)
+    }
+}
+
+/// Visited set split into independently locked shards
+struct ConcurrentSet<C> {
+    shards: Vec<Mutex<HashSet<C>>>,
+    hasher: RandomState,
+}
+
+impl<C: Eq + Hash> ConcurrentSet<C> {
+    const SHARDS: usize = 64;
+
+    fn new() -> ConcurrentSet<C> {
+        ConcurrentSet {
+            shards: (0..Self::SHARDS).map(|_| Mutex::new(HashSet::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn insert(&self, c: C) -> bool {
+        let shard = self.hasher.hash_one(&c) as usize % Self::SHARDS;
+        self.shards[shard].lock().unwrap().insert(c)
+    }
+}
+
+/// Level-synchronous breadth-first search that expands each frontier
+/// across all cores. Returns every reachable state grouped by depth, the
+/// same states and depths `Bfs::layers` produces, though the order of
+/// states within a layer depends on thread timing.
+///
+/// Each layer spawns its own scoped threads, at most one per core, which
+/// claim fixed chunks of 256 frontier states from a shared cursor until
+/// the layer is exhausted; a layer of one chunk runs on the calling
+/// thread. Chunks are not split or stolen once claimed, and every layer
+/// pays for spawning and joining its threads, so this only pays off for
+/// wide frontiers with expensive transitions.
+pub fn par_layers<S, C, I, J, TFn, CFn>(
+    initials: J,
+    transition: TFn,
+    characterize: CFn,
+) -> Vec<Vec<S>>
+    where
+        S: Send + Sync,
+        C: Eq + Hash + Send,
+        TFn: Fn(&S) -> I + Sync,
+        I: IntoIterator<Item = S>,
+        CFn: Fn(&S) -> C + Sync,
+        J: IntoIterator<Item = S>,
+{
+    const CHUNK: usize = 256;
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let visited = ConcurrentSet::new();
+
+    let mut frontier: Vec<S> = initials
+        .into_iter()
+        .filter(|s| visited.insert(characterize(s)))
+        .collect();
+    let mut layers = vec![];
+
+    while !frontier.is_empty() {
+        let cursor = AtomicUsize::new(0);
+        let next = Mutex::new(vec![]);
+        let work = || {
+            let mut found = vec![];
+            loop {
+                let start = cursor.fetch_add(CHUNK, Ordering::Relaxed);
+                if start >= frontier.len() {
+                    break;
+                }
+                for s in &frontier[start..frontier.len().min(start + CHUNK)] {
+                    for n in transition(s) {
+                        if visited.insert(characterize(&n)) {
+                            found.push(n)
+                        }
+                    }
+                }
+            }
+            next.lock().unwrap().extend(found);
+        };
+
+        let workers = cores.min(frontier.len().div_ceil(CHUNK));
+        if workers <= 1 {
+            work()
+        } else {
+            thread::scope(|scope| {
+                for _ in 0..workers {
+                    scope.spawn(work);
+                }
+            });
+        }
+
+        layers.push(std::mem::replace(&mut frontier, next.into_inner().unwrap()));
     }
-}
\ No newline at end of file
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collatz(&n: &u64) -> impl Iterator<Item = u64> {
+        let back = if n % 6 == 4 && n > 4 { Some((n - 1) / 3) } else { None };
+        std::iter::once(n * 2).chain(back)
+    }
+
+    #[test]
+    fn paths_and_depths() {
+        let mut bfs = Bfs::new(1, collatz, |&n| n);
+        assert_eq!(bfs.find(|&n| n == 3), Some(3));
+        assert_eq!(bfs.depth(&3), Some(7));
+        assert_eq!(bfs.keys_to(&3), vec![1, 2, 4, 8, 16, 5, 10, 3]);
+        assert_eq!(bfs.keys_to(&1), vec![1]);
+        assert!(bfs.keys_to(&7).is_empty());
+        assert_eq!(bfs.depth(&7), None);
+    }
+
+    #[test]
+    fn stateful_transitions() {
+        let mut expanded = vec![];
+        let transition = |&n: &u64| {
+            expanded.push(n);
+            collatz(&n)
+        };
+        let found = Bfs::new(1, transition, |&n| n).position(|n| n == 3);
+        assert_eq!(found, Some(12));
+        assert_eq!(expanded.len(), 13);
+    }
+
+    #[test]
+    fn states_are_moved_not_cloned() {
+        // a state that can't be cloned, so the search has to hand it over
+        #[derive(Debug, PartialEq)]
+        struct Token(u64);
+        let transition = |t: &Token| collatz(&t.0).map(Token);
+        let mut bfs = Bfs::new(Token(1), transition, |t| t.0);
+        assert_eq!(bfs.find(|t| t.0 == 5), Some(Token(5)));
+        assert_eq!(bfs.keys_to(&5), vec![1, 2, 4, 8, 16, 5]);
+    }
+
+    #[test]
+    fn multiple_sources() {
+        let mut bfs = Bfs::from_sources(vec![1, 3], collatz, |&n| n);
+        assert_eq!(bfs.find(|&n| n == 12), Some(12));
+        assert_eq!(bfs.depth(&12), Some(2));
+        assert_eq!(bfs.keys_to(&12), vec![3, 6, 12]);
+    }
+
+    #[test]
+    fn bidirectional_paths() {
+        fn forward(&n: &u64) -> Vec<u64> {
+            vec![n + 1, n * 2]
+        }
+        fn backward(&n: &u64) -> Vec<u64> {
+            let mut prev = vec![];
+            if n > 0 {
+                prev.push(n - 1)
+            }
+            if n % 2 == 0 && n > 0 {
+                prev.push(n / 2)
+            }
+            prev
+        }
+        let path = bidirectional(vec![1], vec![37], forward, backward, |&n| n).unwrap();
+        let mut expected = Bfs::new(1, forward, |&n| n);
+        expected.find(|&n| n == 37);
+        assert_eq!(path.len(), expected.depth(&37).unwrap() + 1);
+        assert_eq!(path.first(), Some(&1));
+        assert_eq!(path.last(), Some(&37));
+        assert!(path.windows(2).all(|w| forward(&w[0]).contains(&w[1])));
+
+        assert_eq!(bidirectional(vec![5], vec![5], forward, backward, |&n| n), Some(vec![5]));
+        // odd and even numbers below 10 never meet
+        let up = |&n: &u64| if n < 10 { vec![n + 2] } else { vec![] };
+        let down = |&n: &u64| if n >= 2 { vec![n - 2] } else { vec![] };
+        assert_eq!(bidirectional(vec![1], vec![4], up, down, |&n| n), None);
+    }
+
+    #[test]
+    fn layers_and_limits() {
+        let layers: Vec<Vec<u64>> = Bfs::new(1, collatz, |&n| n).max_depth(5).layers().collect();
+        assert_eq!(layers, vec![vec![1], vec![2], vec![4], vec![8], vec![16], vec![32, 5]]);
+
+        let depths: Vec<(usize, u64)> = Bfs::from_sources(vec![1, 3], collatz, |&n| n).max_depth(1).with_depths().collect();
+        assert_eq!(depths, vec![(0, 1), (0, 3), (1, 2), (1, 6)]);
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        use crate::pos::{Dir, Pos};
+
+        // an open 300x300 grid with a wall along every third column
+        let open = |p: Pos| 0 <= p.x && p.x < 300 && 0 <= p.y && p.y < 300 && (p.x % 3 != 2 || p.y == 299);
+        let moves = |&p: &Pos| Dir::ELEMS.iter().map(move |&d| p.advance(d, 1)).filter(move |&q| open(q)).collect::<Vec<_>>();
+
+        let mut sequential: Vec<Vec<Pos>> = Bfs::new(Pos::ORIGIN, moves, |&p| p).layers().collect();
+        let mut parallel = par_layers(vec![Pos::ORIGIN], moves, |&p| p);
+        for layer in sequential.iter_mut().chain(parallel.iter_mut()) {
+            layer.sort()
+        }
+        assert_eq!(parallel.len(), sequential.len());
+        assert_eq!(parallel, sequential);
+    }
+}
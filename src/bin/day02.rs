@@ -1,4 +1,5 @@
-use advent::intcode::{Addr,Machine};
+use advent::intcode::{search, Addr, Machine, Patch};
+use itertools::iproduct;
 
 fn main() {
     let input = advent::load_input_file(2);
@@ -8,9 +9,16 @@ fn main() {
 }
 
 fn eval(pgm: &[i64], x: i64, y: i64) -> i64 {
-    let mut machine = Machine::new(pgm.to_vec());
-    machine[Addr(1)] = x;
-    machine[Addr(2)] = y;
+    run(Machine::new(pgm.to_vec()), x, y)
+}
+
+fn run(machine: Machine, x: i64, y: i64) -> i64 {
+    let mut machine = machine
+        .with_patches(vec![
+            Patch::new("noun", Addr(1), x),
+            Patch::new("verb", Addr(2), y),
+        ])
+        .unwrap();
     let _ = machine.step();
     machine[Addr(0)]
 }
@@ -20,14 +28,11 @@ fn solve1(pgm: &[i64]) -> i64 {
 }
 
 fn solve2(pgm: &[i64]) -> i64 {
-    for x in 0..100 {
-        for y in 0..100 {
-            if eval(pgm, x, y) == 19690720 {
-                return x * 100 + y;
-            }
-        }
-    }
-    panic!("no answer");
+    let params: Vec<(i64, i64)> = iproduct!(0..100, 0..100).collect();
+    search::find(&Machine::new(pgm.to_vec()), &params, |m, &(x, y)| {
+        if run(m, x, y) == 19690720 { Some(x * 100 + y) } else { None }
+    })
+    .expect("no answer")
 }
 
 #[cfg(test)]
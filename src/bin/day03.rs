@@ -1,57 +1,51 @@
-use advent::pos::Pos;
+use advent::pos::{Alphabet, Dir, Path, Pos};
 use std::str::FromStr;
-use std::iter;
-use std::collections::HashMap;
 
 fn main() {
-    let mut input: Vec<Vec<Cmd>> = advent::load_input_file(3).lines().map(parse_path).collect();
-    let path2 = input.remove(1);
-    let path1 = input.remove(0);
-
-    let mut grid1: HashMap<Pos, usize> = HashMap::new();
-    generate_path(path1)
-        .enumerate()
-        .for_each(|(i, x)| { grid1.insert(x, i + 1); });
-
-    let mut p1 = i64::max_value();
-    let mut p2 = usize::max_value();
-    for (i, x) in generate_path(path2).enumerate() {
-        if let Some(&j) = grid1.get(&x) {
-            p1 = p1.min(x.norm1());
-            p2 = p2.min(1 + i + j)
-        }
-    }
+    let input = advent::load_input_file(3);
+    let (p1, p2) = solve(&input);
     println!("Part 1: {}", p1);
     println!("Part 2: {}", p2);
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Cmd {
-    direction: Pos,
-    distance: i64,
+/// Distance to the crossing closest to the origin and the fewest combined
+/// steps to reach a crossing
+fn solve(input: &str) -> (i64, i64) {
+    let mut paths: Vec<Path> = input.lines().map(parse_path).collect();
+    let path2 = paths.remove(1);
+    let path1 = paths.remove(0);
+
+    let mut crossings = path1.crossings(&path2);
+    crossings.remove(&Pos::ORIGIN);
+    let p1 = crossings.keys().map(|p| p.norm1()).min().unwrap();
+    let p2 = crossings.values().map(|(a, b)| a + b).min().unwrap();
+    (p1, p2)
 }
 
-fn parse1(string: &str) -> Cmd {
-    let direction = match &string[0..1] {
-        "U" => Pos { x: 0, y: -1 },
-        "D" => Pos { x: 0, y: 1 },
-        "L" => Pos { x: -1, y: 0 },
-        "R" => Pos { x: 1, y: 0 },
-        _ => panic!("Bad direction"),
-    };
+fn parse1(string: &str) -> (Dir, i64) {
+    let direction = Dir::parse_with(&string[0..1], Alphabet::Udlr).unwrap();
     let distance = i64::from_str(&string[1..]).unwrap();
-    Cmd { direction, distance }
+    (direction, distance)
 }
 
-fn parse_path(string: &str) -> Vec<Cmd> {
-    string.split(',').map(parse1).collect()
+fn parse_path(string: &str) -> Path {
+    Path::new(Pos::ORIGIN, string.split(',').map(parse1))
 }
 
-fn generate_path(cmds: Vec<Cmd>) -> impl Iterator<Item=Pos> {
-    cmds.into_iter()
-        .flat_map(|cmd| iter::repeat(cmd.direction).take(cmd.distance as usize))
-        .scan(Pos::ORIGIN, |st, x| {
-            *st += x;
-            Some(*st)
-        })
-}
\ No newline at end of file
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn examples() {
+        assert_eq!(solve("R8,U5,L5,D3\nU7,R6,D4,L4"), (6, 30));
+        assert_eq!(
+            solve("R75,D30,R83,U83,L12,D49,R71,U7,L72\nU62,R66,U55,R34,D71,R55,D58,R83"),
+            (159, 610)
+        );
+        assert_eq!(
+            solve("R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\nU98,R91,D20,R16,D67,R40,U7,R15,U6,R7"),
+            (135, 410)
+        );
+    }
+}
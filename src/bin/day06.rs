@@ -1,3 +1,4 @@
+use advent::bfs::{bidirectional, Bfs};
 use std::collections::HashMap;
 
 fn main() {
@@ -15,32 +16,24 @@ fn main() {
         forward.get_mut(k1).unwrap().push(k2);
     }
 
-    let mut horizon = vec!["COM"];
-
-    let mut p1 = 0;
-    let mut distance = 0;
-    let z = vec![];
-    while !horizon.is_empty() {
-        p1 += horizon.len() * distance;
-        horizon = horizon.into_iter().flat_map(|x|
-            forward.get(x).unwrap_or(&z)).copied().collect();
-        distance += 1;
-    }
+    // Every object at depth d is orbiting d objects, directly or not
+    let orbiters = |&k: &&str| forward.get(k).cloned().unwrap_or_default();
+    let p1: usize = Bfs::new("COM", orbiters, |&k| k)
+        .layers()
+        .enumerate()
+        .map(|(distance, layer)| distance * layer.len())
+        .sum();
 
     println!("Part 1: {}", p1);
 
-    let mut p1 = path(&backward, "SAN");
-    let mut p2 = path(&backward, "YOU");
-
-    while p1.last() == p2.last() {
-        p1.pop();
-        p2.pop();
-    }
+    // Orbits can be traversed in either direction
+    let neighbors = |&k: &&str| -> Vec<&str> {
+        forward.get(k).into_iter().flatten().copied().chain(backward.get(k).copied()).collect()
+    };
+    let route = bidirectional(vec!["YOU"], vec!["SAN"], neighbors, neighbors, |&k| k)
+        .expect("no route to Santa");
 
-    let part2 = p1.len() + p2.len() - 2;
+    // Transfers are counted between the objects YOU and SAN are orbiting
+    let part2 = route.len() - 3;
     println!("Part 2: {}", part2);
 }
-
-fn path<'a>(parents: &'a HashMap<&'a str, &'a str>, start: &'a str) -> Vec<&'a str> {
-    std::iter::successors(Some(start), |&k| parents.get(&k).copied()).collect()
-}
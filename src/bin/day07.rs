@@ -39,10 +39,7 @@ fn make_controller(pgm: &[i64], params: &[i64]) -> impl Iterator<Item = i64> {
         .fold(loopbox, |it, &param| {
             Box::new(machine(pgm.to_vec(), once(param).chain(it)))
         })
-        .map(move |x| {
-            loopback.borrow_mut().push_back(x);
-            x
-        })
+        .inspect(move |&x| loopback.borrow_mut().push_back(x))
 }
 
 #[cfg(test)]
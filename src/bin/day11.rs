@@ -1,7 +1,7 @@
 use advent::intcode::parse_program;
 use advent::intcode::iterator::machine;
-use std::collections::HashMap;
-use advent::pos::Pos;
+use advent::grid::SparseGrid;
+use advent::pos::{Dir, Pos, Turn};
 use std::cell::Cell;
 use itertools::Itertools;
 
@@ -22,29 +22,24 @@ fn solve2() {
     let pgm = parse_program(&input).unwrap();
     let paint = painter(pgm, 1);
 
-    let min_x = paint.keys().map(|x|x.x).min().unwrap();
-    let min_y = paint.keys().map(|x|x.y).min().unwrap();
-    let max_x = paint.keys().map(|x|x.x).max().unwrap();
-    let max_y = paint.keys().map(|x|x.y).max().unwrap();
-
-    for y in min_y ..= max_y {
-        for x in min_x ..= max_x {
-            let c = match paint.get(&Pos{x,y}) {
-                Some(0) => '░',
-                Some(1) => '█',
-                None => ' ',
-                _ => '!',
-            };
-            print!("{}", c)
-        }
-        println!();
+    // Panels the robot never painted are left blank
+    let mut hull = SparseGrid::new(' ');
+    for (p, &color) in paint.iter() {
+        hull.insert(p, match color {
+            0 => '░',
+            1 => '█',
+            _ => '!',
+        });
     }
+    print!("{}", hull.render(|&c| c, &[]));
 }
 
-fn painter(pgm: Vec<i64>, start: i64) -> HashMap<Pos, i64> {
+/// Run the painting robot over an all-black hull, with the starting panel
+/// painted `start`. Only panels the robot painted are stored.
+fn painter(pgm: Vec<i64>, start: i64) -> SparseGrid<i64> {
     let mut here = Pos::ORIGIN;
-    let mut paint = HashMap::new();    
-    let mut dir = Pos { x:0, y:-1 };
+    let mut paint = SparseGrid::new(0);
+    let mut dir = Dir::N;
     let current = Cell::new(start);
 
     paint.insert(here, start);
@@ -55,21 +50,13 @@ fn painter(pgm: Vec<i64>, start: i64) -> HashMap<Pos, i64> {
         let color = command.next().unwrap();
         let look = command.next().unwrap();
         paint.insert(here, color);
-        dir = turn(dir, look);
-        here += dir;
-        current.set(paint.get(&here).copied().unwrap_or(0))
+        dir = dir.apply(Turn::from_code(look).expect("bad turn code"));
+        here = here.advance(dir, 1);
+        current.set(*paint.get(here))
     }
     paint
 }
 
-fn turn(dir: Pos, turn: i64) -> Pos {
-    match turn {
-        0 => dir.turn_counterclockwise(),
-        1 => dir.turn_clockwise(),
-        _ => panic!("bad turn code"),
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
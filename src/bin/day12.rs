@@ -1,84 +1,78 @@
+use advent::cycle;
+use advent::point::Point;
 use gcd::Gcd;
 use regex::Regex;
 use std::str::FromStr;
 
-const DIM: usize = 3;
-
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-struct Element {
-    pos: i64,
-    vel: i64,
+struct Moon<const N: usize> {
+    pos: Point<N>,
+    vel: Point<N>,
 }
 
-fn main() {
-    let input: Vec<[i64; DIM]> = parse(&advent::load_input_file(12));
-    let sims = transpose_sim(input);
-    println!("Part 1: {}", part1(&sims));
-    println!("Part 2: {}", part2(&sims));
-}
+impl<const N: usize> Moon<N> {
+    fn at_rest(pos: Point<N>) -> Self {
+        Moon { pos, vel: Point::ORIGIN }
+    }
 
-fn transpose_sim(input: Vec<[i64; DIM]>) -> [Vec<Element>; DIM] {
-    let f = |i| {
-        input
-            .iter()
-            .map(|a| Element { pos: a[i], vel: 0 })
-            .collect()
-    };
-    [f(0), f(1), f(2)]
+    fn energy(&self) -> i64 {
+        self.pos.norm1() * self.vel.norm1()
+    }
+
+    /// The same moon as seen along a single axis
+    fn axis(&self, i: usize) -> Moon<1> {
+        Moon { pos: Point([self.pos[i]]), vel: Point([self.vel[i]]) }
+    }
 }
 
-fn part1(inputs: &[Vec<Element>]) -> i64 {
-    let dims: Vec<Vec<Element>> = inputs
-        .iter()
-        .cloned()
-        .map(|mut dim| {
-            for _ in 0..1000 {
-                step_simulation(&mut dim)
-            }
-            dim
-        })
-        .collect();
+fn main() {
+    let moons: Vec<Moon<3>> = parse(&advent::load_input_file(12));
+    println!("Part 1: {}", part1(&moons));
+    println!("Part 2: {}", part2(&moons));
+}
 
-    dims[0]
-        .iter()
-        .zip(&dims[1])
-        .zip(&dims[2])
-        .map(|((x, y), z)| {
-            (x.pos.abs() + y.pos.abs() + z.pos.abs()) * (x.vel.abs() + y.vel.abs() + z.vel.abs())
-        })
-        .sum()
+fn part1(moons: &[Moon<3>]) -> i64 {
+    let mut moons = moons.to_vec();
+    for _ in 0..1000 {
+        step_simulation(&mut moons)
+    }
+    moons.iter().map(Moon::energy).sum()
 }
 
-fn part2(input: &[Vec<Element>]) -> u64 {
-    input
-        .iter()
-        .map(|sim| cycle_length(sim))
+/// The axes are simulated independently, so the whole system repeats once
+/// every axis has.
+fn part2(moons: &[Moon<3>]) -> u64 {
+    (0..3)
+        .map(|i| {
+            let axis: Vec<Moon<1>> = moons.iter().map(|m| m.axis(i)).collect();
+            cycle_length(&axis)
+        })
         .reduce(lcm)
         .unwrap()
 }
 
-fn parse(input: &str) -> Vec<[i64; DIM]> {
+fn parse(input: &str) -> Vec<Moon<3>> {
     let regex: Regex = Regex::new("<x=(-?[0-9]+), y=(-?[0-9]+), z=(-?[0-9]+)>").unwrap();
     input
         .lines()
         .map(|line| {
             let caps = regex.captures(line).unwrap();
             let f = |i| i64::from_str(&caps[i]).unwrap();
-            [f(1), f(2), f(3)]
+            Moon::at_rest(Point([f(1), f(2), f(3)]))
         })
         .collect()
 }
 
-fn step_simulation(elements: &mut [Element]) {
-    for i in 0..elements.len() {
-        for j in i + 1..elements.len() {
-            let acceleration = (elements[i].pos - elements[j].pos).signum();
-            elements[i].vel -= acceleration;
-            elements[j].vel += acceleration;
+fn step_simulation<const N: usize>(moons: &mut [Moon<N>]) {
+    for i in 0..moons.len() {
+        for j in i + 1..moons.len() {
+            let acceleration = (moons[i].pos - moons[j].pos).signum();
+            moons[i].vel -= acceleration;
+            moons[j].vel += acceleration;
         }
     }
-    for element in elements {
-        element.pos += element.vel
+    for moon in moons {
+        moon.pos += moon.vel
     }
 }
 
@@ -86,15 +80,30 @@ fn lcm(x: u64, y: u64) -> u64 {
     x / x.gcd(y) * y
 }
 
-fn cycle_length(elements: &[Element]) -> u64 {
-    let mut current = elements.to_vec();
-    step_simulation(&mut current);
-    for n in 1.. {
-        if current == elements {
-            return n;
-        } else {
-            step_simulation(&mut current);
+/// Steps until the simulation first returns to its initial state. The
+/// simulation can be run backwards, so it never leaves a cycle.
+fn cycle_length<const N: usize>(moons: &[Moon<N>]) -> u64 {
+    cycle::period_of_reversible(&moons.to_vec(), |current| step_simulation(current)) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+<x=-8, y=-10, z=0>
+<x=5, y=5, z=10>
+<x=2, y=-7, z=3>
+<x=9, y=-8, z=-3>";
+
+    #[test]
+    fn example() {
+        let moons = parse(EXAMPLE);
+        let mut after = moons.clone();
+        for _ in 0..100 {
+            step_simulation(&mut after)
         }
+        assert_eq!(after.iter().map(Moon::energy).sum::<i64>(), 1940);
+        assert_eq!(part2(&moons), 4686774924);
     }
-    panic!("overflow")
 }
@@ -1,4 +1,5 @@
-use advent::intcode::iterator::machine;
+use advent::intcode::iterator::{machine, resume};
+use advent::intcode::{Addr, Machine, Patch};
 use itertools::Itertools;
 use std::cell::Cell;
 use std::collections::HashMap;
@@ -27,15 +28,20 @@ fn part1(pgm: Vec<i64>) -> usize {
         .count()
 }
 
-fn part2(mut pgm: Vec<i64>) -> i64 {
+/// Insert quarters so the cabinet can be played indefinitely
+fn free_play() -> Patch {
+    Patch::new("free-play", Addr(0), 2).expecting(1)
+}
+
+fn part2(pgm: Vec<i64>) -> i64 {
     let ball: Cell<i64> = Default::default();
     let plat: Cell<i64> = Default::default();
     let mut score: i64 = 0;
-    pgm[0] = 2; // enable free play mode
+    let m = Machine::new(pgm).with_patches(vec![free_play()]).unwrap();
 
     let inputs = std::iter::from_fn(|| Some((ball.get() - plat.get()).signum()));
 
-    for mut chunk in machine(pgm, inputs).chunks(3).into_iter() {
+    for mut chunk in resume(m, inputs).chunks(3).into_iter() {
         let x = chunk.next().unwrap();
         let y = chunk.next().unwrap();
         let t = chunk.next().unwrap();
@@ -1,71 +1,155 @@
 use advent::intcode::{Machine, Step, parse_program};
 use advent::bfs::Bfs;
+use advent::grid::SparseGrid;
 use advent::pos::{Pos, Dir};
+use std::collections::{HashMap, HashSet};
 
 fn main() {
-    let (p1,p2) = solve();
-    println!("Part 1: {}", p1);
-    println!("Part 2: {}", p2);
+    let survey = survey();
+    println!("Part 1: {}", survey.distance);
+    println!("Part 2: {}", survey.fill_time);
+    print!("{}", render(&survey));
 }
 
-fn solve() -> (u64, u64) {
+/// Everything the droid learns about the maze
+struct Survey {
+    /// Length of the shortest route to the oxygen system
+    distance: usize,
+    /// Minutes for oxygen to fill the whole maze
+    fill_time: usize,
+    /// Positions along the shortest route, from the origin to the oxygen system
+    route: Vec<Pos>,
+    /// Every open position in the maze
+    open: HashSet<Pos>,
+}
+
+fn survey() -> Survey {
     let input = advent::load_input_file(15);
     let pgm = parse_program(&input).unwrap();
-    let m = Machine::new(pgm);
+    let m = Machine::new(pgm).with_state_tracking();
 
-    let s1 = robot_bfs(m)
+    let mut search = robot_bfs(m);
+    let s1 = search
         .find(|s| s.found)
         .expect("oxygen not found");
-    let s2 = robot_bfs(s1.machine).last().unwrap();
+    let oxygen = s1.pos;
+    let found = key(&s1);
+    let route = search.keys_to(&found).into_iter().map(|(p, _)| p).collect();
 
+    // Oxygen spreads one layer of the search from the oxygen system per
+    // minute. The droid's memory also records its last move, so a position
+    // can be reached again in a later layer; its first layer counts.
+    let mut fill: HashMap<Pos, usize> = HashMap::new();
+    for (minute, layer) in robot_bfs(s1.machine).layers().enumerate() {
+        for s in layer {
+            // the fill search is relative to the oxygen system
+            fill.entry(s.pos + oxygen).or_insert(minute);
+        }
+    }
 
-    (s1.distance, s2.distance)
+    Survey {
+        distance: search.depth(&found).unwrap(),
+        fill_time: *fill.values().max().unwrap(),
+        route,
+        open: fill.into_keys().collect(),
+    }
+}
+
+/// Draw the explored maze with the shortest route to the oxygen system
+fn render(survey: &Survey) -> String {
+    let mut maze = SparseGrid::new(false);
+    for &p in &survey.open {
+        maze.insert(p, true);
+    }
+    // unexplored cells read as walls; widen the bounds to show the walls
+    // surrounding the open area
+    let area = maze.bounds().unwrap().expand(1);
+    maze.insert(area.min, false);
+    maze.insert(area.max, false);
+
+    let oxygen = *survey.route.last().unwrap();
+    let mut overlays: Vec<(Pos, char)> = survey.route.iter().map(|&p| (p, 'o')).collect();
+    overlays.push((Pos::ORIGIN, 'D'));
+    overlays.push((oxygen, 'O'));
+    maze.render(|&open| if open { ' ' } else { '█' }, &overlays)
 }
 
 #[derive(Clone)]
 struct MazeState {
     pos: Pos,
     found: bool,
-    distance: u64,
     machine: Machine,
 }
 
-fn robot_bfs(machine: Machine) -> impl Iterator<Item=MazeState> {
-    fn transition(s: &MazeState) -> Vec<MazeState> {
-        Dir::ELEMS.iter().flat_map(
-            |&dir| {
-                let mut machine = s.machine.clone();
-                let found =
-                    match move_robot(&mut machine, dir) {
-                        Response::Stuck => return None,
-                        Response::Moved => false,
-                        Response::Found => true,
-                    };
-                Some(
-                    MazeState {
-                        pos: s.pos.advance(dir, 1),
-                        distance: s.distance + 1,
-                        found,
-                        machine,
-                    })
-            }
-        ).collect()
+/// Successor states of a maze state, probing one direction at a time.
+/// A droid that bumps into a wall stays put, so its machine can go on to
+/// probe the next direction; only successful moves need a fresh copy.
+struct Moves {
+    pos: Pos,
+    base: Machine,
+    spare: Option<Machine>,
+    dirs: std::slice::Iter<'static, Dir>,
+}
+
+impl Iterator for Moves {
+    type Item = MazeState;
+    fn next(&mut self) -> Option<MazeState> {
+        for &dir in &mut self.dirs {
+            let base = &self.base;
+            let mut machine = self.spare.take().unwrap_or_else(|| base.clone());
+            let found =
+                match move_robot(&mut machine, dir) {
+                    Response::Stuck => {
+                        self.spare = Some(machine);
+                        continue
+                    }
+                    Response::Moved => false,
+                    Response::Found => true,
+                };
+            return Some(
+                MazeState {
+                    pos: self.pos.advance(dir, 1),
+                    found,
+                    machine,
+                })
+        }
+        None
+    }
+}
+
+/// Maze states are told apart by the droid's full machine state, which
+/// already determines its position. The position is kept for mapping.
+type Key = (Pos, Machine);
+
+fn key(s: &MazeState) -> Key {
+    (s.pos, s.machine.clone())
+}
+
+type MazeBfs = Bfs<MazeState, Key, fn(&MazeState) -> Moves, fn(&MazeState) -> Key>;
+
+fn robot_bfs(machine: Machine) -> MazeBfs {
+    fn transition(s: &MazeState) -> Moves {
+        Moves {
+            pos: s.pos,
+            base: s.machine.clone(),
+            spare: None,
+            dirs: Dir::ELEMS.iter(),
+        }
     }
 
     Bfs::new(
         MazeState {
             pos: Pos::ORIGIN,
             found: false,
-            distance: 0,
             machine,
         },
         transition,
-        |s| s.pos)
+        key)
 }
 
 fn move_robot(machine: &mut Machine, dir: Dir) -> Response {
     match machine.step() {
-        Ok(Step::Input(i)) => machine[i] = dir_to_i64(dir),
+        Ok(Step::Input(i)) => machine[i] = dir.code(),
         _ => panic!("bad machine state"),
     }
 
@@ -84,24 +168,26 @@ enum Response {
     Found,
 }
 
-
-fn dir_to_i64(dir: Dir) -> i64 {
-    match dir {
-        Dir::N => 1,
-        Dir::S => 2,
-        Dir::W => 3,
-        Dir::E => 4,
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn solve_input() {
-        let (p1, p2) = solve();
-        assert_eq!(p1, 242);
-        assert_eq!(p2, 276);
+        let survey = survey();
+        assert_eq!(survey.distance, 242);
+        assert_eq!(survey.fill_time, 276);
+    }
+
+    #[test]
+    fn render_maze() {
+        let route = vec![Pos::ORIGIN, Pos { x: 1, y: 0 }, Pos { x: 2, y: 0 }];
+        let survey = Survey {
+            distance: 2,
+            fill_time: 3,
+            open: route.iter().copied().chain(Some(Pos { x: 2, y: 1 })).collect(),
+            route,
+        };
+        assert_eq!(render(&survey), "█████\n█DoO█\n███ █\n█████\n");
     }
 }
\ No newline at end of file
@@ -1,18 +1,13 @@
 use std::collections::HashSet;
-use advent::intcode::iterator::machine;
-use advent::pos::{Dir, Pos};
+use advent::intcode::iterator::{machine, resume};
+use advent::intcode::{Addr, Machine, Patch};
+use advent::graph::intersections;
+use advent::grid::Grid;
+use advent::pos::{Dir, Pos, Turn};
 
 const SUBLEN: usize = 20;
 const SUBNAMES: [&str; 3] = ["A", "B", "C"];
 
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
-enum Turn {
-    /// Right turn
-    R,
-    /// Left turn
-    L,
-}
-
 /// A sequence of turns and steps to give to the robot
 type Instructions<'a> = &'a [(Turn, i64)];
 
@@ -26,41 +21,37 @@ fn main() {
     println!("Part 2: {}", part2(pgm, &world, start));
 }
 
-/// Predicate for locations surrounded by positions in the world
-fn is_surrounded(world: &HashSet<Pos>, p: Pos) -> bool {
-    Dir::ELEMS.iter().all(|&x| world.contains(&p.advance(x, 1)))
-}
-
 /// Computes part 1 score for locations of girder intersections
 fn part1(world: &HashSet<Pos>) -> i64 {
-    world
-        .iter()
-        .filter(|&&p| is_surrounded(world, p))
-        .map(|p| p.x * p.y)
-        .sum()
+    intersections(world).map(|p| p.x * p.y).sum()
+}
+
+/// Wake up the vacuum robot so that it accepts movement routines
+fn wake_up() -> Patch {
+    Patch::new("wake-up", Addr(0), 2).expecting(1)
 }
 
 /// Compute a program that guides the robot across the whole world map,
 /// run that program, and find the final score value.
-fn part2(mut pgm: Vec<i64>, world: &HashSet<Pos>, start: Pos) -> i64 {
+fn part2(pgm: Vec<i64>, world: &HashSet<Pos>, start: Pos) -> i64 {
     // Solve the uncompressed robot instruction sequence
-    let path = world_path(&world, start);
+    let path = world_path(world, start);
 
     // Compute the intcode program input that solves the puzzle
     let input_string = build_program(&path);
     let input = input_string.chars().map(|x| x as i64);
 
     // Switch program into interactive mode
-    pgm[0] = 2;
+    let m = Machine::new(pgm).with_patches(vec![wake_up()]).unwrap();
 
     // Run program with computed input values and return the final output value
-    machine(pgm, input).last().unwrap()
+    resume(m, input).last().unwrap()
 }
 
 // Compute the ASCII input that solves the robot puzzle
 fn build_program(path: Instructions) -> String {
     // Search for a valid main and sub routines
-    let (main_routine, subroutines) = program_search(&path).unwrap();
+    let (main_routine, subroutines) = program_search(path).unwrap();
 
     // Build corresponding text input
     let mut input_string = main_routine
@@ -70,7 +61,7 @@ fn build_program(path: Instructions) -> String {
         .join(",");
     input_string += "\n";
     for subroutine in subroutines {
-        input_string += &render_instructions(&subroutine);
+        input_string += &render_instructions(subroutine);
         input_string += "\n";
     }
     input_string += "no\n";
@@ -79,27 +70,13 @@ fn build_program(path: Instructions) -> String {
 
 // Convert intcode output into a starting location and world map
 fn scan_map(output: &str) -> (HashSet<Pos>, Pos) {
-    let mut start = Pos::ORIGIN;
-    let mut world = HashSet::new();
-    for (y, line) in output.lines().enumerate() {
-        for (x, cell) in line.chars().enumerate() {
-            let p = Pos {
-                x: x as i64,
-                y: y as i64,
-            };
-            match cell {
-                '#' => {
-                    world.insert(p);
-                }
-                '^' => {
-                    world.insert(p);
-                    start = p
-                }
-                '.' => {}
-                _ => panic!("unexpected cell: {}", cell),
-            }
-        }
-    }
+    let cell = |c| match c {
+        '#' | '^' | '.' => Some(c),
+        _ => None,
+    };
+    let grid = Grid::from_str(output.trim_end(), cell).unwrap();
+    let world = grid.find(|&c| c != '.').collect();
+    let start = grid.find(|&c| c == '^').next().expect("robot not found");
     (world, start)
 }
 
@@ -110,18 +87,15 @@ fn world_path(world: &HashSet<Pos>, mut here: Pos) -> Vec<(Turn, i64)> {
     let mut face = Dir::N;
 
     'outer: loop {
-        for (turn, op) in [
-            (Turn::R, Dir::turn_clockwise as fn(Dir) -> Dir),
-            (Turn::L, Dir::turn_around),
-        ] {
-            face = op(face);
+        for turn in [Turn::Right, Turn::Left] {
+            let dir = face.apply(turn);
             let found = (1..)
-                .into_iter()
-                .map(|n| (n, here.advance(face, n)))
+                .map(|n| (n, here.advance(dir, n)))
                 .take_while(|(_, p)| world.contains(p))
                 .last();
             if let Some((n, p)) = found {
                 result.push((turn, n));
+                face = dir;
                 here = p;
                 continue 'outer;
             }
@@ -134,7 +108,7 @@ fn world_path(world: &HashSet<Pos>, mut here: Pos) -> Vec<(Turn, i64)> {
 fn render_instructions(instructions: Instructions) -> String {
     instructions
         .iter()
-        .map(|&(t, n)| format!("{:?},{}", t, n))
+        .map(|&(t, n)| format!("{},{}", t, n))
         .collect::<Vec<String>>()
         .join(",")
 }
@@ -194,3 +168,16 @@ fn program_search(target: Instructions) -> Option<(Vec<usize>, Vec<Instructions>
     Some((main_routine,
     subroutines.into_iter().map(|(a,b)| &target[a..b]).collect()))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersections_need_four_sides() {
+        // (1,1) is an intersection; (3,1) is only a T-junction
+        let (world, start) = scan_map(".#...\n####^\n.#.#.\n");
+        assert_eq!(start, Pos { x: 4, y: 1 });
+        assert_eq!(part1(&world), 1);
+    }
+}
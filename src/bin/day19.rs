@@ -1,4 +1,5 @@
-use advent::intcode::{parse_program, Machine};
+use advent::intcode::{parse_program, search, Machine};
+use advent::pos::{Pos, Rect};
 
 fn main() {
     let input = advent::load_input_file(19);
@@ -18,15 +19,11 @@ fn query(mut m: Machine, x: i64, y: i64) -> bool {
 }
 
 fn part1(m: Machine) -> usize {
-    let mut n: usize = 0;
-    for x in 0..50 {
-        for y in 0..50 {
-            if query(m.clone(), x, y) {
-                n += 1;
-            }
-        }
-    }
-    n
+    let params: Vec<Pos> = Rect::from_size(Pos::ORIGIN, 50, 50).positions().collect();
+    search::map(&m, &params, |m, p| query(m, p.x, p.y))
+        .into_iter()
+        .filter(|&hit| hit)
+        .count()
 }
 
 fn part2(m: Machine) -> i64 {
@@ -37,8 +34,10 @@ fn part2(m: Machine) -> i64 {
         while !query(m.clone(), x, y) {
             x += 1
         }
-        if query(m.clone(), x+99, y-99) {
-            break x * 10_000 + y-99
+        // the ship's bottom-left corner is in the beam; check the top-right
+        let ship = Rect::from_size(Pos { x, y: y-99 }, 100, 100);
+        if query(m.clone(), ship.max.x, ship.min.y) {
+            break ship.min.x * 10_000 + ship.min.y
         }
         y += 1
     }
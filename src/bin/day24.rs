@@ -1,4 +1,7 @@
-use advent::pos::{Dir, Pos};
+use advent::bitgrid::BitGrid;
+use advent::cycle::History;
+use advent::grid::Grid;
+use advent::pos::{Pos, Transform};
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 
@@ -6,55 +9,35 @@ fn main() {
     let input = advent::load_input_file(24);
     let initial = parse_input(input);
     println!("Part 1: {}", part1(&initial));
-    println!("Part 2: {}", part2(&initial));
+    println!("Part 2: {}", part2(&initial, 200));
 }
 
-fn biodiversity(cells: HashSet<Pos>) -> u64 {
-    let mut val = 1;
-    let mut res = 0;
-    for y in 0..5 {
-        for x in 0..5 {
-            if cells.contains(&Pos { x, y }) {
-                res += val
-            }
-            val *= 2;
-        }
-    }
-    res
+/// Sum of the powers of two of each bug's position in row-major order
+fn biodiversity(cells: &BitGrid) -> u64 {
+    cells.to_bits() as u64
 }
 
 fn parse_input(input: String) -> Vec<Pos> {
-    let mut result = vec![];
-    for (y, line) in input.lines().enumerate() {
-        for (x, cell) in line.char_indices() {
-            if cell == '#' {
-                result.push(Pos {
-                    x: x as i64,
-                    y: y as i64,
-                })
-            }
-        }
-    }
-    result
+    let cell = |c| match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    };
+    Grid::from_str(&input, cell).unwrap().find(|&bug| bug).collect()
 }
 
-fn members<P: Copy + Ord>(world: &HashSet<P>) -> Vec<P> {
-    let mut v = vec![];
-    for &x in world {
-        v.push(x)
-    }
-    v.sort();
-    v
+fn part1(initial: &[Pos]) -> u64 {
+    let initial = BitGrid::from_positions(5, 5, initial.iter().copied());
+    // the first layout to appear twice is the first one on the cycle
+    let history = History::detect(initial, step1);
+    biodiversity(&history.states[history.cycle.prefix])
 }
 
-fn part1(initial: &[Pos]) -> u64 {
-    let mut seen: HashSet<Vec<Pos>> = HashSet::new();
-    let mut current: HashSet<Pos> = initial.iter().copied().collect();
-    while !seen.contains(&members(&current)) {
-        seen.insert(members(&current));
-        current = step(&current, neighbors1)
-    }
-    biodiversity(current)
+/// A bug survives with exactly one adjacent bug, and an empty cell is
+/// infested with one or two.
+fn step1(current: &BitGrid) -> BitGrid {
+    let [_, one, two, _, _] = current.neighbor_counts();
+    one | !current.clone() & two
 }
 
 fn step<P, F>(start: &HashSet<P>, next: F) -> HashSet<P>
@@ -78,42 +61,28 @@ where
         .collect()
 }
 
-fn neighbors1(pos: Pos) -> Vec<Pos> {
-    Dir::ELEMS
-        .iter()
-        .map(|&x| pos.advance(x, 1))
-        .filter(|p| 0 <= p.x && 0 <= p.y && p.x < 5 && p.y < 5)
-        .collect()
-}
-
-fn part2(initial: &[Pos]) -> usize {
+fn part2(initial: &[Pos], minutes: usize) -> usize {
     let mut current: HashSet<(Pos, i64)> = HashSet::new();
     for &p in initial {
         current.insert((p - Pos { x: 2, y: 2 }, 0));
     }
 
-    for _ in 0..200 {
+    for _ in 0..minutes {
         current = step(&current, neighbors2)
     }
 
     current.len()
 }
 
+/// Neighbors on the recursive grids, with positions relative to the center
+/// tile. Only the western neighbors are worked out; each rotation of the
+/// grid turns another direction into west.
 fn neighbors2((pos, level): (Pos, i64)) -> Vec<(Pos, i64)> {
-
-    type Pair<X> = (X, X);
-    type PosFn = fn(Pos) -> Pos;
-    let xforms: [Pair<PosFn>; 4] = [
-        (|x| x, |x| x),
-        (Pos::turn_around, Pos::turn_around),
-        (Pos::turn_clockwise, Pos::turn_counterclockwise),
-        (Pos::turn_counterclockwise, Pos::turn_clockwise),
-    ];
-
     let mut result = vec![];
-    for (f, g) in xforms {
-        let mut pos = f(pos);
-        let mut emit = |p, l| result.push((g(p), l));
+    for rotation in Transform::rotations() {
+        let mut pos = rotation.apply(pos);
+        let back = rotation.inverse();
+        let mut emit = |p, l| result.push((back.apply(p), l));
 
         if pos.x == -2 {
             emit(Pos { x: -1, y: 0 }, level - 1)
@@ -128,3 +97,15 @@ fn neighbors2((pos, level): (Pos, i64)) -> Vec<(Pos, i64)> {
     }
     result
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let initial = parse_input("....#\n#..#.\n#..##\n..#..\n#....\n".to_string());
+        assert_eq!(part1(&initial), 2129920);
+        assert_eq!(part2(&initial, 10), 99);
+    }
+}
@@ -0,0 +1,252 @@
+//! Small fixed-size grids of booleans packed into integers, one `u64` per
+//! row, for cellular automata and other states that need cheap hashing.
+
+use crate::pos::Pos;
+use std::collections::HashSet;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+/// Set of positions within `0..width` by `0..height`. Bit `x` of row `y`
+/// holds the cell at `Pos { x, y }`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BitGrid {
+    width: usize,
+    rows: Vec<u64>,
+}
+
+impl BitGrid {
+    /// Empty grid; `width` can be at most 64
+    pub fn new(width: usize, height: usize) -> BitGrid {
+        assert!(width <= 64, "BitGrid width {} exceeds 64", width);
+        BitGrid { width, rows: vec![0; height] }
+    }
+
+    /// Grid containing `positions`, which must all be in bounds
+    pub fn from_positions<I>(width: usize, height: usize, positions: I) -> BitGrid
+    where
+        I: IntoIterator<Item = Pos>,
+    {
+        let mut grid = BitGrid::new(width, height);
+        for p in positions {
+            grid.insert(p);
+        }
+        grid
+    }
+
+    /// Grid from the low `width * height` bits of `bits` in row-major order
+    pub fn from_bits(width: usize, height: usize, bits: u128) -> BitGrid {
+        assert!(width * height <= 128, "BitGrid too large for u128");
+        let mut grid = BitGrid::new(width, height);
+        for (y, row) in grid.rows.iter_mut().enumerate() {
+            *row = (bits >> (y * width)) as u64 & row_mask(width);
+        }
+        grid
+    }
+
+    /// Cells in row-major order, with the cell at the origin in the lowest bit
+    pub fn to_bits(&self) -> u128 {
+        assert!(self.width * self.height() <= 128, "BitGrid too large for u128");
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(y, &row)| (row as u128) << (y * self.width))
+            .sum()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Row and bit index of a position, if it's in bounds
+    fn locate(&self, p: Pos) -> Option<(usize, u64)> {
+        if 0 <= p.x && (p.x as usize) < self.width && 0 <= p.y && (p.y as usize) < self.height() {
+            Some((p.y as usize, 1 << p.x))
+        } else {
+            None
+        }
+    }
+
+    /// Positions outside the grid are never contained
+    pub fn contains(&self, p: Pos) -> bool {
+        self.locate(p).is_some_and(|(y, bit)| self.rows[y] & bit != 0)
+    }
+
+    /// Add a position, returning whether it was newly added. Panics when
+    /// the position is out of bounds.
+    pub fn insert(&mut self, p: Pos) -> bool {
+        let (y, bit) = self.locate(p).unwrap_or_else(|| panic!("{:?} outside BitGrid", p));
+        let added = self.rows[y] & bit == 0;
+        self.rows[y] |= bit;
+        added
+    }
+
+    /// Remove a position, returning whether it was present
+    pub fn remove(&mut self, p: Pos) -> bool {
+        match self.locate(p) {
+            Some((y, bit)) => {
+                let present = self.rows[y] & bit != 0;
+                self.rows[y] &= !bit;
+                present
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.iter().map(|row| row.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&row| row == 0)
+    }
+
+    /// Contained positions in row-major order
+    pub fn iter(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, &row)| {
+            (0..64)
+                .filter(move |x| row >> x & 1 != 0)
+                .map(move |x| Pos { x, y: y as i64 })
+        })
+    }
+
+    /// For each count from 0 to 4, the cells of the grid with exactly that
+    /// many orthogonal neighbors in the set. Cells outside the grid count
+    /// as empty.
+    pub fn neighbor_counts(&self) -> [BitGrid; 5] {
+        let mask = row_mask(self.width);
+        let mut planes = [self.empty(), self.empty(), self.empty()];
+        for y in 0..self.height() {
+            let west = self.rows[y] << 1 & mask;
+            let east = self.rows[y] >> 1;
+            let north = if y > 0 { self.rows[y - 1] } else { 0 };
+            let south = self.rows.get(y + 1).copied().unwrap_or(0);
+
+            // add the four neighbor bits in parallel with full adders
+            let (sum1, carry1) = (west ^ east, west & east);
+            let (sum2, carry2) = (north ^ south, north & south);
+            let carry3 = sum1 & sum2;
+            planes[0].rows[y] = sum1 ^ sum2;
+            planes[1].rows[y] = carry1 ^ carry2 ^ carry3;
+            planes[2].rows[y] = carry1 & carry2 | (carry1 ^ carry2) & carry3;
+        }
+
+        let exactly = |n: usize| {
+            let mut grid = !self.empty();
+            for (i, plane) in planes.iter().enumerate() {
+                let plane = plane.clone();
+                grid = grid & if n >> i & 1 == 1 { plane } else { !plane };
+            }
+            grid
+        };
+        [exactly(0), exactly(1), exactly(2), exactly(3), exactly(4)]
+    }
+
+    fn empty(&self) -> BitGrid {
+        BitGrid::new(self.width, self.height())
+    }
+
+    fn zip_rows(mut self, rhs: BitGrid, f: fn(u64, u64) -> u64) -> BitGrid {
+        assert_eq!((self.width, self.height()), (rhs.width, rhs.height()), "BitGrid sizes differ");
+        for (row, other) in self.rows.iter_mut().zip(rhs.rows) {
+            *row = f(*row, other);
+        }
+        self
+    }
+}
+
+fn row_mask(width: usize) -> u64 {
+    if width == 64 { u64::MAX } else { (1 << width) - 1 }
+}
+
+impl From<&BitGrid> for HashSet<Pos> {
+    fn from(grid: &BitGrid) -> Self {
+        grid.iter().collect()
+    }
+}
+
+impl Not for BitGrid {
+    type Output = BitGrid;
+    fn not(mut self) -> BitGrid {
+        let mask = row_mask(self.width);
+        for row in &mut self.rows {
+            *row = !*row & mask;
+        }
+        self
+    }
+}
+
+impl BitAnd for BitGrid {
+    type Output = BitGrid;
+    fn bitand(self, rhs: BitGrid) -> BitGrid {
+        self.zip_rows(rhs, |a, b| a & b)
+    }
+}
+
+impl BitOr for BitGrid {
+    type Output = BitGrid;
+    fn bitor(self, rhs: BitGrid) -> BitGrid {
+        self.zip_rows(rhs, |a, b| a | b)
+    }
+}
+
+impl BitXor for BitGrid {
+    type Output = BitGrid;
+    fn bitxor(self, rhs: BitGrid) -> BitGrid {
+        self.zip_rows(rhs, |a, b| a ^ b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::pos::Dir;
+
+    fn grid(rows: &[&str]) -> BitGrid {
+        let cells = Grid::from_str(&rows.join("\n"), |c| Some(c == '#')).unwrap();
+        let mut g = BitGrid::new(cells.width(), cells.height());
+        for p in cells.find(|&bug| bug) {
+            g.insert(p);
+        }
+        g
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut g = grid(&["#....", "..#..", ".....", ".....", "....#"]);
+        assert_eq!(g.len(), 3);
+        assert!(g.contains(Pos { x: 2, y: 1 }));
+        assert!(!g.contains(Pos { x: -1, y: 0 }));
+        assert!(!g.insert(Pos { x: 0, y: 0 }));
+        assert!(g.remove(Pos { x: 0, y: 0 }));
+        assert!(!g.remove(Pos { x: 7, y: 0 }));
+
+        let set: HashSet<Pos> = (&g).into();
+        assert_eq!(BitGrid::from_positions(5, 5, set), g);
+        assert_eq!(BitGrid::from_bits(5, 5, g.to_bits()), g);
+        assert_eq!(g.to_bits(), 1 << 7 | 1 << 24);
+        assert_eq!((!g.clone()).len(), 23);
+        assert!((g.clone() ^ g).is_empty());
+    }
+
+    #[test]
+    fn counts_match_naive() {
+        let g = grid(&["#..#.", "##.##", "#.#.#", ".###.", "#...#"]);
+        let counts = g.neighbor_counts();
+        for y in 0..5 {
+            for x in 0..5 {
+                let p = Pos { x, y };
+                let n = Dir::ELEMS
+                    .iter()
+                    .filter(|&&d| g.contains(p.advance(d, 1)))
+                    .count();
+                for (i, plane) in counts.iter().enumerate() {
+                    assert_eq!(plane.contains(p), i == n, "{:?} has {} neighbors", p, n);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Detection of repeating states in deterministic simulations.
+//!
+//! A simulation is an initial state and a step function. Since each state
+//! determines the next, once any state repeats the sequence loops forever:
+//! after `prefix` steps it cycles with period `length`.
+//!
+//! `floyd` and `brent` only need `Eq` and keep a constant number of states
+//! but rerun the simulation; `History::detect` runs it once and keeps every
+//! state, which needs `Hash`. Simulations that can be run backwards never
+//! leave their cycle, so `period_of_reversible` only has to step a single
+//! state in place until it returns to the start.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Cycle {
+    /// Steps taken before entering the cycle
+    pub prefix: usize,
+    /// Number of steps around the cycle
+    pub length: usize,
+}
+
+impl Cycle {
+    /// The earliest step whose state equals the state at step `n`
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.prefix {
+            n
+        } else {
+            self.prefix + (n - self.prefix) % self.length
+        }
+    }
+
+    /// State after `n` steps, found by running only `self.reduce(n)` steps
+    pub fn state_at<S, F>(&self, initial: &S, mut step: F, n: usize) -> S
+    where
+        S: Clone,
+        F: FnMut(&S) -> S,
+    {
+        let mut state = initial.clone();
+        for _ in 0..self.reduce(n) {
+            state = step(&state)
+        }
+        state
+    }
+}
+
+/// Floyd's tortoise and hare cycle detection
+pub fn floyd<S, F>(initial: &S, mut step: F) -> Cycle
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    let mut tortoise = step(initial);
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        hare = step(&hare);
+    }
+
+    let mut prefix = 0;
+    tortoise = initial.clone();
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    let mut length = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        length += 1;
+    }
+
+    Cycle { prefix, length }
+}
+
+/// Brent's cycle detection, which takes fewer steps than `floyd`
+pub fn brent<S, F>(initial: &S, mut step: F) -> Cycle
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    tortoise = initial.clone();
+    hare = initial.clone();
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut prefix = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+
+    Cycle { prefix, length }
+}
+
+/// Steps until a reversible simulation first returns to its initial state.
+/// Only one state is kept and `step` updates it in place. The search never
+/// ends if the initial state isn't on a cycle.
+pub fn period_of_reversible<S, F>(initial: &S, mut step: F) -> usize
+where
+    S: Clone + Eq,
+    F: FnMut(&mut S),
+{
+    let mut state = initial.clone();
+    let mut length = 0;
+    loop {
+        step(&mut state);
+        length += 1;
+        if state == *initial {
+            return length;
+        }
+    }
+}
+
+/// Every state of a simulation up to its first repeat
+#[derive(Debug, Clone)]
+pub struct History<S> {
+    pub cycle: Cycle,
+    /// States from the initial state through the last state before the
+    /// first repeat
+    pub states: Vec<S>,
+}
+
+impl<S: Clone + Eq + Hash> History<S> {
+    /// Run the simulation until a state repeats, recording every state
+    pub fn detect<F>(initial: S, mut step: F) -> History<S>
+    where
+        F: FnMut(&S) -> S,
+    {
+        let mut seen: HashMap<S, usize> = HashMap::new();
+        let mut states = vec![];
+        let mut state = initial;
+        loop {
+            if let Some(&prefix) = seen.get(&state) {
+                let length = states.len() - prefix;
+                return History { cycle: Cycle { prefix, length }, states };
+            }
+            let next = step(&state);
+            seen.insert(state.clone(), states.len());
+            states.push(state);
+            state = next;
+        }
+    }
+
+    /// State after `n` steps of the simulation
+    pub fn state_at(&self, n: usize) -> &S {
+        &self.states[self.cycle.reduce(n)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 6 -> 3 -> ...
+    fn rho(&n: &u64) -> u64 {
+        if n == 6 { 3 } else { n + 1 }
+    }
+
+    const RHO: Cycle = Cycle { prefix: 3, length: 4 };
+
+    #[test]
+    fn detectors_agree() {
+        assert_eq!(floyd(&0, rho), RHO);
+        assert_eq!(brent(&0, rho), RHO);
+        let history = History::detect(0, rho);
+        assert_eq!(history.cycle, RHO);
+        assert_eq!(history.states, vec![0, 1, 2, 3, 4, 5, 6]);
+
+        // a pure cycle has no prefix
+        assert_eq!(brent(&3, rho), Cycle { prefix: 0, length: 4 });
+        assert_eq!(floyd(&6, rho), Cycle { prefix: 0, length: 4 });
+        assert_eq!(period_of_reversible(&5, |n| *n = rho(n)), 4);
+        assert_eq!(period_of_reversible(&vec![1, 2, 3, 4, 5, 6], |v| v.rotate_left(4)), 3);
+    }
+
+    #[test]
+    fn extrapolation() {
+        let history = History::detect(0, rho);
+        for n in 0..50 {
+            let mut expected = 0;
+            for _ in 0..n {
+                expected = rho(&expected)
+            }
+            assert_eq!(*history.state_at(n), expected);
+            assert_eq!(RHO.state_at(&0, rho, n), expected);
+        }
+        assert_eq!(*history.state_at(1_000_000_000), 4);
+    }
+}
@@ -0,0 +1,162 @@
+//! Compression of grid mazes into weighted graphs between points of
+//! interest, such as keys and doors or portal entrances.
+
+use crate::bfs::Bfs;
+use crate::grid::{Grid, ParseError};
+use crate::pos::{Dir, Pos};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+
+/// Weighted undirected graph between the points of interest of a maze
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Graph<L> {
+    /// Label of each point of interest
+    pub nodes: BTreeMap<Pos, L>,
+    /// Length of the shortest corridor between each pair of points of
+    /// interest that doesn't pass through another point of interest
+    pub edges: BTreeMap<Pos, BTreeMap<Pos, usize>>,
+}
+
+/// Open positions adjacent to a position
+fn open_neighbors(open: &HashSet<Pos>, p: Pos) -> impl Iterator<Item = Pos> + '_ {
+    Dir::ELEMS
+        .iter()
+        .map(move |&d| p.advance(d, 1))
+        .filter(move |q| open.contains(q))
+}
+
+/// Positions where two corridors cross, with all four sides open
+pub fn intersections(open: &HashSet<Pos>) -> impl Iterator<Item = Pos> + '_ {
+    open.iter()
+        .copied()
+        .filter(move |&p| open_neighbors(open, p).count() == Dir::ELEMS.len())
+}
+
+/// Open positions of a rectangular map where `#` and spaces are walls,
+/// paired with the character at each position.
+pub fn parse_map(map: &str) -> Result<BTreeMap<Pos, char>, ParseError> {
+    let grid = Grid::from_str(map, Some)?;
+    Ok(grid.iter().filter(|&(_, &c)| c != '#' && c != ' ').map(|(p, &c)| (p, c)).collect())
+}
+
+impl<L> Graph<L> {
+    /// Compress the open positions of a maze into a graph between the
+    /// positions that `label` identifies as points of interest.
+    pub fn compress<F>(open: &HashSet<Pos>, mut label: F) -> Graph<L>
+    where
+        F: FnMut(Pos) -> Option<L>,
+    {
+        let nodes: BTreeMap<Pos, L> = open.iter().filter_map(|&p| Some((p, label(p)?))).collect();
+
+        let mut edges = BTreeMap::new();
+        for &start in nodes.keys() {
+            // Corridors end at the first point of interest they reach
+            let transition = |&p: &Pos| {
+                let stop = p != start && nodes.contains_key(&p);
+                open_neighbors(open, p).filter(move |_| !stop)
+            };
+            let reached: BTreeMap<Pos, usize> = Bfs::new(start, transition, |&p| p)
+                .with_depths()
+                .filter(|&(_, p)| p != start && nodes.contains_key(&p))
+                .map(|(d, p)| (p, d))
+                .collect();
+            edges.insert(start, reached);
+        }
+
+        Graph { nodes, edges }
+    }
+
+    /// Compress a map where `#` and spaces are walls, using `label` to pick
+    /// the points of interest from the map's characters.
+    pub fn from_map<F>(map: &str, mut label: F) -> Result<Graph<L>, ParseError>
+    where
+        F: FnMut(char) -> Option<L>,
+    {
+        let cells = parse_map(map)?;
+        let open = cells.keys().copied().collect();
+        Ok(Graph::compress(&open, |p| label(cells[&p])))
+    }
+
+    /// Points of interest directly reachable from a node, with distances
+    pub fn neighbors(&self, p: Pos) -> impl Iterator<Item = (Pos, usize)> + '_ {
+        self.edges.get(&p).into_iter().flatten().map(|(&q, &d)| (q, d))
+    }
+
+    /// Position of the node with the given label
+    pub fn find(&self, label: &L) -> Option<Pos>
+    where
+        L: PartialEq,
+    {
+        self.nodes.iter().find(|(_, l)| *l == label).map(|(&p, _)| p)
+    }
+
+    /// Render the graph in Graphviz DOT syntax
+    pub fn to_dot(&self) -> String
+    where
+        L: Display,
+    {
+        let mut out = String::from("graph maze {\n");
+        for (p, l) in &self.nodes {
+            out += &format!("    \"{},{}\" [label=\"{}\"];\n", p.x, p.y, l);
+        }
+        for (p, ends) in &self.edges {
+            for (q, d) in ends {
+                // each edge is stored in both directions; draw it once
+                if p < q {
+                    out += &format!("    \"{},{}\" -- \"{},{}\" [label={}];\n", p.x, p.y, q.x, q.y, d);
+                }
+            }
+        }
+        out += "}\n";
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "\
+#########
+#b.A.@.a#
+#.#####.#
+#...c...#
+#########";
+
+    #[test]
+    fn compress_keys_and_doors() {
+        let g = Graph::from_map(MAP, |c| if c == '.' { None } else { Some(c) }).unwrap();
+        assert_eq!(g.nodes.len(), 5);
+
+        let at = g.find(&'@').unwrap();
+        let a = g.find(&'a').unwrap();
+        let door = g.find(&'A').unwrap();
+        let c = g.find(&'c').unwrap();
+        let mut from_entrance: Vec<(Pos, usize)> = g.neighbors(at).collect();
+        from_entrance.sort();
+        assert_eq!(from_entrance, vec![(door, 2), (a, 2)]);
+
+        // 'b' is only reachable through the door or around through 'c'
+        let b = g.find(&'b').unwrap();
+        let mut from_b: Vec<(Pos, usize)> = g.neighbors(b).collect();
+        from_b.sort();
+        assert_eq!(from_b, vec![(b + Pos { x: 2, y: 0 }, 2), (c, 5)]);
+
+        let dot = g.to_dot();
+        assert!(dot.starts_with("graph maze {"));
+        assert_eq!(dot.matches(" -- ").count(), 5);
+    }
+
+    #[test]
+    fn find_intersections() {
+        let open: HashSet<Pos> = parse_map(MAP).unwrap().into_keys().collect();
+        assert_eq!(intersections(&open).count(), 0);
+
+        let plus: HashSet<Pos> = parse_map("#.#\n...\n#.#").unwrap().into_keys().collect();
+        assert_eq!(intersections(&plus).collect::<Vec<_>>(), vec![Pos { x: 1, y: 1 }]);
+
+        // a T-junction is not an intersection
+        let tee: HashSet<Pos> = parse_map("...\n#.#").unwrap().into_keys().collect();
+        assert_eq!(intersections(&tee).count(), 0);
+    }
+}
@@ -0,0 +1,374 @@
+//! Grids indexed by `Pos`: dense rectangles for puzzle maps, and sparse
+//! unbounded grids for worlds explored a cell at a time.
+
+use crate::pos::{Dir, Pos, Rect, Transform};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// Rectangle of cells covering `0..width` by `0..height`, stored in
+/// row-major order
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    cells: Vec<T>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    /// A line's length differs from the first line's
+    Ragged { line: usize, expected: usize, found: usize },
+    /// The mapping rejected a character
+    BadChar { pos: Pos, found: char },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Ragged { line, expected, found } => {
+                write!(f, "line {} has {} cells, expected {}", line, found, expected)
+            }
+            ParseError::BadChar { pos, found } => {
+                write!(f, "unexpected {:?} at {},{}", found, pos.x, pos.y)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl<T> Grid<T> {
+    /// Grid with every cell computed from its position
+    pub fn from_fn<F>(width: usize, height: usize, f: F) -> Grid<T>
+    where
+        F: FnMut(Pos) -> T,
+    {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Pos::from_indices(x, y).expect("grid too large")))
+            .map(f)
+            .collect();
+        Grid { width, cells }
+    }
+
+    /// Parse one cell per character, one row per line. Every line must be
+    /// the same length and `f` must accept every character.
+    pub fn from_str<F>(s: &str, mut f: F) -> Result<Grid<T>, ParseError>
+    where
+        F: FnMut(char) -> Option<T>,
+    {
+        let mut width = None;
+        let mut cells = vec![];
+        for (y, line) in s.lines().enumerate() {
+            let start = cells.len();
+            for (x, c) in line.chars().enumerate() {
+                // a line can't hold more characters than fit in an i64
+                let pos = Pos::from_indices(x, y).unwrap();
+                cells.push(f(c).ok_or(ParseError::BadChar { pos, found: c })?);
+            }
+            let found = cells.len() - start;
+            let expected = *width.get_or_insert(found);
+            if found != expected {
+                return Err(ParseError::Ragged { line: y + 1, expected, found });
+            }
+        }
+        Ok(Grid { width: width.unwrap_or(0), cells })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    fn offset(&self, p: Pos) -> Option<usize> {
+        if self.in_bounds(p) {
+            Some(p.y as usize * self.width + p.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn in_bounds(&self, p: Pos) -> bool {
+        0 <= p.x && (p.x as usize) < self.width && 0 <= p.y && (p.y as usize) < self.height()
+    }
+
+    pub fn get(&self, p: Pos) -> Option<&T> {
+        self.offset(p).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, p: Pos) -> Option<&mut T> {
+        self.offset(p).map(move |i| &mut self.cells[i])
+    }
+
+    /// Every position in row-major order
+    pub fn positions(&self) -> impl Iterator<Item = Pos> {
+        let width = self.width;
+        (0..self.cells.len()).map(move |i| Pos::from_indices(i % width, i / width).expect("grid too large"))
+    }
+
+    /// Every cell with its position in row-major order
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.positions().zip(&self.cells)
+    }
+
+    /// Positions of the cells satisfying a predicate
+    pub fn find<'a, P>(&'a self, mut predicate: P) -> impl Iterator<Item = Pos> + 'a
+    where
+        P: FnMut(&T) -> bool + 'a,
+    {
+        self.iter().filter(move |(_, c)| predicate(c)).map(|(p, _)| p)
+    }
+
+    /// In-bounds positions orthogonally adjacent to a position
+    pub fn neighbors(&self, p: Pos) -> impl Iterator<Item = Pos> + '_ {
+        Dir::ELEMS
+            .iter()
+            .map(move |&d| p.advance(d, 1))
+            .filter(move |&q| self.in_bounds(q))
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks panics on zero; an empty grid has no rows either way
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.width, "column {} out of bounds", x);
+        self.cells.iter().skip(x).step_by(self.width)
+    }
+
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        Grid { width: self.width, cells: self.cells.iter().map(f).collect() }
+    }
+
+    /// Rotate or reflect the grid. Only the symmetry part of the transform
+    /// matters: the result is moved back to start at the origin.
+    pub fn transform(&self, t: Transform) -> Grid<T>
+    where
+        T: Clone,
+    {
+        if self.cells.is_empty() {
+            return Grid { width: 0, cells: vec![] };
+        }
+        // opposite corners stay opposite under every symmetry; a grid's
+        // dimensions are bounded by its cell count, so they fit in an i64
+        let far = Pos::from_indices(self.width - 1, self.height() - 1).unwrap();
+        let (a, b) = (t.linear(Pos::ORIGIN), t.linear(far));
+        let corner = Pos { x: a.x.min(b.x), y: a.y.min(b.y) };
+        let width = (b.x - a.x).unsigned_abs() as usize + 1;
+        let height = (b.y - a.y).unsigned_abs() as usize + 1;
+        let back = t.inverse();
+        Grid::from_fn(width, height, |p| self[back.linear(p + corner)].clone())
+    }
+
+    /// Render one character per cell, one line per row
+    pub fn display<F>(&self, f: F) -> Display<'_, T, F>
+    where
+        F: Fn(&T) -> char,
+    {
+        Display { grid: self, f }
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+    fn index(&self, p: Pos) -> &T {
+        let i = self.offset(p).unwrap_or_else(|| panic!("{:?} outside grid", p));
+        &self.cells[i]
+    }
+}
+
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, p: Pos) -> &mut T {
+        let i = self.offset(p).unwrap_or_else(|| panic!("{:?} outside grid", p));
+        &mut self.cells[i]
+    }
+}
+
+/// A grid rendered with a mapping from cells to characters
+pub struct Display<'a, T, F> {
+    grid: &'a Grid<T>,
+    f: F,
+}
+
+impl<T, F: Fn(&T) -> char> fmt::Display for Display<'_, T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.grid.rows() {
+            let line: String = row.iter().map(&self.f).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Grid<char> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(|&c| c).fmt(f)
+    }
+}
+
+/// Unbounded grid storing only the cells that were written, which tracks
+/// the bounding box of everything written so far
+#[derive(Debug, Clone)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Pos, T>,
+    default: T,
+    bounds: Option<Rect>,
+}
+
+impl<T> SparseGrid<T> {
+    /// Empty grid where every unwritten cell reads as `default`
+    pub fn new(default: T) -> SparseGrid<T> {
+        SparseGrid { cells: HashMap::new(), default, bounds: None }
+    }
+
+    /// Value of a cell, or the default if it was never written
+    pub fn get(&self, p: Pos) -> &T {
+        self.cells.get(&p).unwrap_or(&self.default)
+    }
+
+    /// Write a cell, returning its previous value if it had been written
+    pub fn insert(&mut self, p: Pos, value: T) -> Option<T> {
+        self.bounds = Some(match self.bounds {
+            None => Rect::new(p, p),
+            Some(r) => r.including(p),
+        });
+        self.cells.insert(p, value)
+    }
+
+    pub fn contains(&self, p: Pos) -> bool {
+        self.cells.contains_key(&p)
+    }
+
+    /// Number of written cells
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Written cells in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.cells.iter().map(|(&p, v)| (p, v))
+    }
+
+    /// Bounding box of the written cells
+    pub fn bounds(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    /// Render the bounding box of the written cells and overlay markers
+    /// one character per cell. Markers replace the cell underneath them;
+    /// later markers win.
+    pub fn render<F>(&self, f: F, overlays: &[(Pos, char)]) -> String
+    where
+        F: Fn(&T) -> char,
+    {
+        let corners = self.bounds.into_iter().flat_map(|r| [r.min, r.max]);
+        let area = match Rect::bounding(corners.chain(overlays.iter().map(|&(p, _)| p))) {
+            Some(area) => area,
+            None => return String::new(),
+        };
+
+        let mut out = String::new();
+        for p in area.positions() {
+            let marker = overlays.iter().rev().find(|&&(q, _)| q == p);
+            out.push(marker.map_or_else(|| f(self.get(p)), |&(_, c)| c));
+            if p.x == area.max.x {
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "#.#\n..^\n##.\n";
+
+    #[test]
+    fn parse_and_render() {
+        let grid = Grid::from_str(MAP, Some).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 3));
+        assert_eq!(grid[Pos { x: 2, y: 1 }], '^');
+        assert_eq!(grid.get(Pos { x: 3, y: 1 }), None);
+        assert_eq!(grid.find(|&c| c == '^').collect::<Vec<_>>(), vec![Pos { x: 2, y: 1 }]);
+        assert_eq!(grid.to_string(), MAP);
+
+        let walls = grid.map(|&c| c == '#');
+        assert_eq!(walls.display(|&w| if w { '█' } else { ' ' }).to_string(), "█ █\n   \n██ \n");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Grid::from_str("...\n..\n", Some),
+            Err(ParseError::Ragged { line: 2, expected: 3, found: 2 })
+        );
+        let open = |c| if c == '.' { Some(()) } else { None };
+        assert_eq!(
+            Grid::from_str("..\n.x\n", open),
+            Err(ParseError::BadChar { pos: Pos { x: 1, y: 1 }, found: 'x' })
+        );
+        assert_eq!(Grid::from_str("", open).map(|g| g.height()), Ok(0));
+    }
+
+    #[test]
+    fn views_and_neighbors() {
+        let mut grid = Grid::from_fn(4, 3, |p| p.x + 10 * p.y);
+        assert_eq!(grid.row(1), &[10, 11, 12, 13]);
+        assert_eq!(grid.column(2).copied().collect::<Vec<_>>(), vec![2, 12, 22]);
+        assert_eq!(grid.rows().count(), 3);
+
+        let corner: Vec<Pos> = grid.neighbors(Pos::ORIGIN).collect();
+        assert_eq!(corner, vec![Pos { x: 0, y: 1 }, Pos { x: 1, y: 0 }]);
+        assert_eq!(grid.neighbors(Pos { x: 1, y: 1 }).count(), 4);
+
+        grid[Pos { x: 3, y: 2 }] = 0;
+        assert_eq!(grid.iter().filter(|&(_, &v)| v == 0).count(), 2);
+    }
+
+    #[test]
+    fn transform_grids() {
+        let grid = Grid::from_str("ab\ncd\nef\n", Some).unwrap();
+        let turned = grid.transform(Transform::CLOCKWISE);
+        assert_eq!(turned.to_string(), "eca\nfdb\n");
+        assert_eq!(grid.transform(Transform::FLIP_X).to_string(), "ba\ndc\nfe\n");
+        for &t in &Transform::symmetries() {
+            assert_eq!(grid.transform(t).transform(t.inverse()), grid);
+        }
+    }
+
+    #[test]
+    fn sparse_bounds_and_render() {
+        let mut grid = SparseGrid::new(false);
+        assert_eq!(grid.bounds(), None);
+        assert_eq!(grid.render(|_| '?', &[]), "");
+
+        grid.insert(Pos { x: -1, y: 2 }, true);
+        grid.insert(Pos { x: 1, y: 0 }, true);
+        assert_eq!(grid.insert(Pos { x: 1, y: 0 }, false), Some(true));
+        assert_eq!(grid.bounds(), Some(Rect::new(Pos { x: -1, y: 0 }, Pos { x: 1, y: 2 })));
+        assert!(!grid.get(Pos { x: 5, y: 5 }));
+        assert_eq!(grid.len(), 2);
+
+        let cell = |&b: &bool| if b { '#' } else { '.' };
+        assert_eq!(grid.render(cell, &[]), "...\n...\n#..\n");
+        // markers extend the rendered area
+        let markers = [(Pos::ORIGIN, 'o'), (Pos { x: 2, y: 1 }, '>')];
+        assert_eq!(grid.render(cell, &markers), ".o..\n...>\n#...\n");
+    }
+}
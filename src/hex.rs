@@ -0,0 +1,128 @@
+//! Hexagonal grids in axial coordinates.
+//!
+//! Hexes are flat-topped: each has neighbors to the north and south and
+//! on the four diagonals. `q` increases to the east and `r` to the south,
+//! so the implicit third cube coordinate is `-q - r`.
+
+use crate::pos::Direction;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Hex {
+    pub q: i64,
+    pub r: i64,
+}
+
+#[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Copy, Clone, Hash)]
+pub enum HexDir {
+    N,
+    NE,
+    SE,
+    S,
+    SW,
+    NW,
+}
+
+impl Hex {
+    pub const ORIGIN: Hex = Hex { q: 0, r: 0 };
+
+    pub fn advance(self, dir: HexDir, n: i64) -> Hex {
+        let unit = dir.unit();
+        Hex {
+            q: self.q + unit.q * n,
+            r: self.r + unit.r * n,
+        }
+    }
+
+    /// Number of steps from the origin
+    pub fn norm(self) -> i64 {
+        (self.q.abs() + self.r.abs() + (self.q + self.r).abs()) / 2
+    }
+
+    /// The six adjacent hexes in clockwise order from the north
+    pub fn neighbors(self) -> impl Iterator<Item = Hex> {
+        HexDir::iter().map(move |d| self.advance(d, 1))
+    }
+}
+
+impl Direction for HexDir {
+    type Vector = Hex;
+    const CLOCKWISE: &'static [HexDir] = &[
+        HexDir::N,
+        HexDir::NE,
+        HexDir::SE,
+        HexDir::S,
+        HexDir::SW,
+        HexDir::NW,
+    ];
+
+    fn unit(self) -> Hex {
+        let (q, r) = match self {
+            HexDir::N => (0, -1),
+            HexDir::NE => (1, -1),
+            HexDir::SE => (1, 0),
+            HexDir::S => (0, 1),
+            HexDir::SW => (-1, 1),
+            HexDir::NW => (-1, 0),
+        };
+        Hex { q, r }
+    }
+}
+
+impl Add for Hex {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl Sub for Hex {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl AddAssign for Hex {
+    fn add_assign(&mut self, rhs: Self) {
+        self.q += rhs.q;
+        self.r += rhs.r;
+    }
+}
+
+impl SubAssign for Hex {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.q -= rhs.q;
+        self.r -= rhs.r;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directions() {
+        for d in HexDir::iter() {
+            assert_eq!(d.unit() + d.opposite().unit(), Hex::ORIGIN);
+            assert_eq!(d.unit().norm(), 1);
+            // adjacent directions are themselves neighbors
+            assert_eq!((d.unit() - d.turn_right().unit()).norm(), 1);
+        }
+        assert_eq!(HexDir::NW.turn_right(), HexDir::N);
+        assert_eq!(HexDir::N.turn_left(), HexDir::NW);
+    }
+
+    #[test]
+    fn distances() {
+        let walk = |dirs: &[HexDir]| dirs.iter().fold(Hex::ORIGIN, |h, &d| h.advance(d, 1)).norm();
+        use HexDir::*;
+        assert_eq!(walk(&[NE, NE, NE]), 3);
+        assert_eq!(walk(&[NE, NE, SW, SW]), 0);
+        assert_eq!(walk(&[NE, NE, S, S]), 2);
+        assert_eq!(walk(&[SE, SW, SE, SW, SW]), 3);
+        assert_eq!(Hex::ORIGIN.neighbors().count(), 6);
+    }
+}
@@ -0,0 +1,325 @@
+//! Control-flow graph recovery and Graphviz export.
+//!
+//! Basic blocks are found by decoding from the entry point and following
+//! fallthrough and immediate jump targets. A jump with an immediate
+//! condition always or never branches, so only its possible successor is
+//! followed; the words after an unconditional jump are often data. Jumps
+//! whose targets come from memory can't be resolved statically; running
+//! the machine under a `Trace` records the targets they actually took,
+//! which are then added to the graph as computed edges.
+
+use super::{Addr, Error, Machine, Op, Step};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Param {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Param::Position(x) => write!(f, "[{}]", x),
+            Param::Immediate(x) => write!(f, "{}", x),
+            Param::Relative(x) => write!(f, "[rb{:+}]", x),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Instruction {
+    pub addr: usize,
+    pub op: Op,
+    pub params: Vec<Param>,
+}
+
+impl Instruction {
+    /// Address of the instruction following this one in memory
+    pub fn next_addr(&self) -> usize {
+        self.addr + 1 + self.params.len()
+    }
+
+    /// Jump target when it is encoded as an immediate parameter
+    fn static_target(&self) -> Option<usize> {
+        match (self.op, self.params.get(1)) {
+            (Op::JumpIfTrue, Some(&Param::Immediate(t))) | (Op::JumpIfFalse, Some(&Param::Immediate(t))) => {
+                usize::try_from(t).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a jump always (`Some(true)`) or never (`Some(false)`)
+    /// branches, when its condition is an immediate parameter
+    fn static_branch(&self) -> Option<bool> {
+        match (self.op, self.params.first()) {
+            (Op::JumpIfTrue, Some(&Param::Immediate(c))) => Some(c != 0),
+            (Op::JumpIfFalse, Some(&Param::Immediate(c))) => Some(c == 0),
+            _ => None,
+        }
+    }
+
+    fn is_jump(&self) -> bool {
+        self.op == Op::JumpIfTrue || self.op == Op::JumpIfFalse
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.addr, self.op.mnemonic())?;
+        for (i, p) in self.params.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, p)?;
+        }
+        Ok(())
+    }
+}
+
+impl Machine {
+    /// Decode the instruction stored at `addr` in the machine's memory
+    pub fn decode(&self, addr: usize) -> Result<Instruction, Error> {
+        let opcode = self.memory.get(addr).copied().unwrap_or(0);
+        let op = Op::decode(opcode)?;
+        let params = (1..=op.arity())
+            .map(|i| {
+                let x = self.memory.get(addr + i).copied().unwrap_or(0);
+                match opcode / i64::pow(10, 1 + i as u32) % 10 {
+                    0 => Ok(Param::Position(x)),
+                    1 => Ok(Param::Immediate(x)),
+                    2 => Ok(Param::Relative(x)),
+                    _ => Err(Error::BadParameterMode),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Instruction { addr, op, params })
+    }
+}
+
+/// Execution counts and control transfers observed while running a machine
+#[derive(Debug, Default, Clone)]
+pub struct Trace {
+    pub counts: HashMap<usize, u64>,
+    pub transfers: HashSet<(usize, usize)>,
+}
+
+impl Trace {
+    /// Run the machine until its next I/O event like `Machine::step`,
+    /// recording every instruction executed along the way. A jump to a
+    /// negative address fails with `Error::BadAddress` once it is taken.
+    pub fn step(&mut self, machine: &mut Machine) -> Result<Step, Error> {
+        loop {
+            let Addr(from) = Addr::try_from(machine.pc())?;
+            let result = machine.step_instruction()?;
+            *self.counts.entry(from).or_insert(0) += 1;
+            let Addr(to) = Addr::try_from(machine.pc())?;
+            self.transfers.insert((from, to));
+            if let Some(step) = result {
+                return Ok(step);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction in memory
+    Next,
+    /// An immediate jump target
+    Jump,
+    /// A jump target loaded from memory and observed in a trace
+    Computed,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    /// Number of times the block was entered, when a trace was given
+    pub count: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    /// Basic blocks keyed by their starting address
+    pub blocks: BTreeMap<usize, Block>,
+    pub edges: BTreeSet<(usize, usize, EdgeKind)>,
+}
+
+impl Cfg {
+    /// Recover the control-flow graph of the machine's current memory
+    /// starting from address 0 and any addresses executed in the trace.
+    pub fn build(machine: &Machine, trace: Option<&Trace>) -> Cfg {
+        let mut computed: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        let mut roots = vec![0];
+        if let Some(trace) = trace {
+            for &(from, to) in &trace.transfers {
+                computed.entry(from).or_default().insert(to);
+                roots.push(to);
+            }
+        }
+
+        // Decode every reachable instruction and its successors
+        let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+        let mut successors: BTreeMap<usize, BTreeSet<(usize, EdgeKind)>> = BTreeMap::new();
+        let mut leaders: BTreeSet<usize> = BTreeSet::from([0]);
+        while let Some(addr) = roots.pop() {
+            if instructions.contains_key(&addr) {
+                continue;
+            }
+            let instruction = match machine.decode(addr) {
+                Ok(i) => i,
+                Err(_) => continue,
+            };
+
+            let mut next = BTreeSet::new();
+            let branch = instruction.static_branch();
+            if instruction.op != Op::Halt && branch != Some(true) {
+                next.insert((instruction.next_addr(), EdgeKind::Next));
+            }
+            if let Some(t) = instruction.static_target().filter(|_| branch != Some(false)) {
+                next.insert((t, EdgeKind::Jump));
+            }
+            for &t in computed.get(&addr).into_iter().flatten() {
+                if t != instruction.next_addr() && Some(t) != instruction.static_target() {
+                    next.insert((t, EdgeKind::Computed));
+                }
+            }
+
+            if instruction.is_jump() || instruction.op == Op::Halt {
+                leaders.extend(next.iter().map(|&(t, _)| t));
+            } else {
+                leaders.extend(next.iter().filter(|e| e.1 == EdgeKind::Computed).map(|&(t, _)| t));
+            }
+            roots.extend(next.iter().map(|&(t, _)| t));
+            successors.insert(addr, next);
+            instructions.insert(addr, instruction);
+        }
+
+        // Group instructions into blocks that end at jumps or leaders
+        let mut cfg = Cfg::default();
+        for &start in &leaders {
+            let mut block = vec![];
+            let mut addr = start;
+            while let Some(instruction) = instructions.get(&addr) {
+                block.push(instruction.clone());
+                let next = instruction.next_addr();
+                let ends = instruction.is_jump()
+                    || instruction.op == Op::Halt
+                    || leaders.contains(&next)
+                    || successors[&addr].iter().any(|e| e.1 == EdgeKind::Computed);
+                if ends {
+                    for &(to, kind) in &successors[&addr] {
+                        if instructions.contains_key(&to) {
+                            cfg.edges.insert((start, to, kind));
+                        }
+                    }
+                    break;
+                }
+                addr = next;
+            }
+            if !block.is_empty() {
+                let count = trace.map(|t| t.counts.get(&start).copied().unwrap_or(0));
+                cfg.blocks.insert(start, Block { instructions: block, count });
+            }
+        }
+        cfg
+    }
+
+    /// Render the graph in Graphviz DOT syntax
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (start, block) in &self.blocks {
+            let mut label = String::new();
+            for instruction in &block.instructions {
+                label += &format!("{}\\l", instruction);
+            }
+            if let Some(count) = block.count {
+                label += &format!("executed {} times\\l", count);
+            }
+            out += &format!("    b{} [label=\"{}\"];\n", start, label);
+        }
+        for (from, to, kind) in &self.edges {
+            let style = match kind {
+                EdgeKind::Next => "solid",
+                EdgeKind::Jump => "bold",
+                EdgeKind::Computed => "dashed",
+            };
+            out += &format!("    b{} -> b{} [style={}];\n", from, to, style);
+        }
+        out += "}\n";
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_blocks() {
+        // the comparison program from day 5
+        let pgm = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let cfg = Cfg::build(&Machine::new(pgm), None);
+        assert_eq!(cfg.blocks.len(), 1);
+        assert_eq!(cfg.blocks[&0].instructions.len(), 4);
+        assert_eq!(cfg.blocks[&0].instructions[1].to_string(), "2: eq [9], [10], [9]");
+        assert!(cfg.edges.is_empty());
+    }
+
+    #[test]
+    fn computed_jumps_from_trace() {
+        // calls a subroutine that returns through an address stored in memory
+        let pgm = vec![
+            1101, 0, 7, 20, // 0: store return address 7 in [20]
+            1105, 1, 10, // 4: call subroutine at 10
+            104, 5, 99, // 7: output 5 and halt
+            104, 4, 5, 21, 20, // 10: output 4 and return
+            99, 0, 0, 0, 0, 0, 1,
+        ];
+        let mut m = Machine::new(pgm);
+
+        let cfg = Cfg::build(&m, None);
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 10, 15]);
+        assert!(cfg.edges.contains(&(0, 10, EdgeKind::Jump)));
+        assert!(cfg.edges.iter().all(|e| e.2 != EdgeKind::Computed));
+
+        let mut trace = Trace::default();
+        let mut outputs = vec![];
+        while let Step::Output(o) = trace.step(&mut m).unwrap() {
+            outputs.push(o)
+        }
+        assert_eq!(outputs, vec![4, 5]);
+
+        let cfg = Cfg::build(&m, Some(&trace));
+        assert!(cfg.edges.contains(&(10, 7, EdgeKind::Computed)));
+        assert_eq!(cfg.blocks[&10].count, Some(1));
+        assert_eq!(cfg.blocks[&15].count, Some(0));
+        assert!(cfg.to_dot().contains("b10 -> b7 [style=dashed];"));
+    }
+
+    #[test]
+    fn constant_conditions() {
+        // an unconditional jump over four words of data
+        let pgm = vec![1106, 0, 7, 1, 0, 0, 0, 99];
+        let cfg = Cfg::build(&Machine::new(pgm), None);
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 7]);
+        assert_eq!(cfg.edges.iter().copied().collect::<Vec<_>>(), vec![(0, 7, EdgeKind::Jump)]);
+
+        // a jump that is never taken only falls through
+        let pgm = vec![1105, 0, 7, 104, 1, 99, 0, 99];
+        let cfg = Cfg::build(&Machine::new(pgm), None);
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 3]);
+        assert_eq!(cfg.edges.iter().copied().collect::<Vec<_>>(), vec![(0, 3, EdgeKind::Next)]);
+    }
+
+    #[test]
+    fn negative_jump_targets() {
+        let mut m = Machine::new(vec![1105, 1, -4]);
+        let mut trace = Trace::default();
+        assert_eq!(trace.step(&mut m), Err(Error::BadAddress));
+        assert_eq!(trace.counts.get(&0), Some(&1));
+        assert!(trace.transfers.is_empty());
+    }
+}
@@ -23,11 +23,18 @@ impl<I: Iterator<Item = i64>> Iterator for MachineIterator<I> {
 }
 
 pub fn machine<I>(pgm: Vec<i64>, inputs: I) -> MachineIterator<I::IntoIter>
+where
+    I: IntoIterator<Item = i64>,
+{
+    resume(Machine::new(pgm), inputs)
+}
+
+pub fn resume<I>(machine: Machine, inputs: I) -> MachineIterator<I::IntoIter>
 where
     I: IntoIterator<Item = i64>,
 {
     MachineIterator {
-        machine: Machine::new(pgm),
+        machine,
         inputs: inputs.into_iter(),
     }
 }
@@ -1,12 +1,20 @@
+pub mod cfg;
 pub mod iterator;
+pub mod patch;
+pub mod program;
+pub mod search;
+pub mod state;
+
+pub use patch::Patch;
 
 use std::convert::{TryInto,TryFrom};
 use std::num::ParseIntError;
 use std::ops::{Index, IndexMut};
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub fn parse_program(input: &str) -> Result<Vec<i64>, ParseIntError> {
-    input.trim().split(',').map(|x| i64::from_str(x)).collect()
+    input.trim().split(',').map(i64::from_str).collect()
 }
 
 #[derive(Debug, Clone)]
@@ -14,7 +22,13 @@ pub struct Machine {
     pc: i64,
     rel_base: i64,
     memory: Vec<i64>,
-
+    /// Log of applied patches, shared rather than copied when forking
+    patches: Arc<[Patch]>,
+    /// Rolling hash of nonzero memory cells once state tracking is
+    /// enabled, see the `state` module
+    memory_hash: Option<u64>,
+    /// Most recently borrowed cell whose value is not yet in `memory_hash`
+    pending_write: Option<usize>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
@@ -46,6 +60,7 @@ impl IndexMut<Addr> for Machine {
             }
             self.memory.resize(new_size, 0)
         }
+        self.track_write(idx);
         &mut self.memory[idx]
     }
 }
@@ -56,9 +71,17 @@ impl Machine {
             pc: 0,
             rel_base: 0,
             memory,
+            memory_hash: None,
+            patches: Arc::new([]),
+            pending_write: None,
         }
     }
 
+    /// Current program counter
+    pub fn pc(&self) -> i64 {
+        self.pc
+    }
+
     fn arg_ptr(&self, opcode: i64, arg: i64) -> Result<Addr, Error> {
         let pos = (self.pc + arg).try_into()?;
         match opcode / i64::pow(10, 1 + arg as u32) % 10 {
@@ -69,50 +92,117 @@ impl Machine {
         }
     }
 
+    /// Run until the machine halts or needs to perform I/O.
     pub fn step(&mut self) -> Result<Step, Error> {
         loop {
-            let opcode = self[self.pc.try_into()?];
-
-            macro_rules! ptr {
-                ( $arg:literal ) => { self.arg_ptr(opcode, $arg)? }
+            if let Some(step) = self.step_instruction()? {
+                return Ok(step);
             }
+        }
+    }
 
-            macro_rules! val {
-                ( $arg:literal ) => { self[ptr!($arg)] };
-            }
+    /// Execute a single instruction, returning the I/O or halt event
+    /// it produced, if any.
+    pub fn step_instruction(&mut self) -> Result<Option<Step>, Error> {
+        let opcode = self[self.pc.try_into()?];
 
-            macro_rules! io {
-                ( $res:expr ) => {{
-                    let r = $res; // <- must be computed before pc updates
-                    self.pc += 2;
-                    break Ok(r);
-                }};
-            }
+        macro_rules! ptr {
+            ( $arg:literal ) => { self.arg_ptr(opcode, $arg)? }
+        }
 
-            macro_rules! compute {
-                ( $val:expr ) => {{
-                    let p = ptr!(3);
-                    self[p] = $val;
-                    self.pc += 4;
-                }};
-            }
+        macro_rules! val {
+            ( $arg:literal ) => { self[ptr!($arg)] };
+        }
+
+        macro_rules! io {
+            ( $res:expr ) => {{
+                let r = $res; // <- must be computed before pc updates
+                self.pc += 2;
+                return Ok(Some(r));
+            }};
+        }
 
-            match opcode % 100 {
-                1 => compute!(val!(1).checked_add(val!(2)).ok_or(Error::ArithmeticOverflow)?),
-                2 => compute!(val!(1).checked_mul(val!(2)).ok_or(Error::ArithmeticOverflow)?),
-                3 => io!(Step::Input(ptr!(1))),
-                4 => io!(Step::Output(val!(1))),
-                5 => if val!(1) != 0 { self.pc = val!(2) } else { self.pc += 3 },
-                6 => if val!(1) == 0 { self.pc = val!(2) } else { self.pc += 3 },
-                7 => compute!((val!(1) < val!(2)) as i64),
-                8 => compute!((val!(1) == val!(2)) as i64),
-                9 => {
-                    self.rel_base += val!(1);
-                    self.pc += 2
-                }
-                99 => break Ok(Step::Halt),
-                _ => break Err(Error::BadOpcode),
+        macro_rules! compute {
+            ( $val:expr ) => {{
+                let p = ptr!(3);
+                self[p] = $val;
+                self.pc += 4;
+            }};
+        }
+
+        match Op::decode(opcode)? {
+            Op::Add => compute!(val!(1).checked_add(val!(2)).ok_or(Error::ArithmeticOverflow)?),
+            Op::Mul => compute!(val!(1).checked_mul(val!(2)).ok_or(Error::ArithmeticOverflow)?),
+            Op::Input => io!(Step::Input(ptr!(1))),
+            Op::Output => io!(Step::Output(val!(1))),
+            Op::JumpIfTrue => if val!(1) != 0 { self.pc = val!(2) } else { self.pc += 3 },
+            Op::JumpIfFalse => if val!(1) == 0 { self.pc = val!(2) } else { self.pc += 3 },
+            Op::LessThan => compute!((val!(1) < val!(2)) as i64),
+            Op::Equals => compute!((val!(1) == val!(2)) as i64),
+            Op::AdjustBase => {
+                self.rel_base += val!(1);
+                self.pc += 2
             }
+            Op::Halt => return Ok(Some(Step::Halt)),
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Op {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+}
+
+impl Op {
+    /// Decode the operation from the low two digits of an opcode
+    pub fn decode(opcode: i64) -> Result<Op, Error> {
+        match opcode % 100 {
+            1 => Ok(Op::Add),
+            2 => Ok(Op::Mul),
+            3 => Ok(Op::Input),
+            4 => Ok(Op::Output),
+            5 => Ok(Op::JumpIfTrue),
+            6 => Ok(Op::JumpIfFalse),
+            7 => Ok(Op::LessThan),
+            8 => Ok(Op::Equals),
+            9 => Ok(Op::AdjustBase),
+            99 => Ok(Op::Halt),
+            _ => Err(Error::BadOpcode),
+        }
+    }
+
+    /// Number of parameters following the opcode
+    pub fn arity(self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => 3,
+            Op::JumpIfTrue | Op::JumpIfFalse => 2,
+            Op::Input | Op::Output | Op::AdjustBase => 1,
+            Op::Halt => 0,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Mul => "mul",
+            Op::Input => "in",
+            Op::Output => "out",
+            Op::JumpIfTrue => "jnz",
+            Op::JumpIfFalse => "jz",
+            Op::LessThan => "lt",
+            Op::Equals => "eq",
+            Op::AdjustBase => "arb",
+            Op::Halt => "halt",
         }
     }
 }
@@ -146,6 +236,8 @@ pub enum Error {
     BadOpcode,
     BadParameterMode,
     ArithmeticOverflow,
+    PatchMismatch,
+    InfiniteLoop,
 }
 
 #[cfg(test)]
@@ -186,6 +278,21 @@ mod tests {
         assert_eq!(run(&pgm, [9]), vec![1001]);
     }
 
+    #[test]
+    fn patches() {
+        let pgm = vec![1, 0, 0, 0, 99];
+        let m = Machine::new(pgm.clone())
+            .with_patches(vec![Patch::new("noun", Addr(1), 4).expecting(0)])
+            .unwrap();
+        assert_eq!(m[Addr(1)], 4);
+        assert_eq!(m.patches()[0].name, "noun");
+
+        let bad = Machine::new(pgm.clone()).with_patches(vec![Patch::new("x", Addr(1), 4).expecting(1)]);
+        assert_eq!(bad.unwrap_err(), Error::PatchMismatch);
+        let bad = Machine::new(pgm).with_patches(vec![Patch::new("x", Addr(5), 4)]);
+        assert_eq!(bad.unwrap_err(), Error::BadAddress);
+    }
+
     #[test]
     fn day02() {
         let input = super::super::load_input_file(2);
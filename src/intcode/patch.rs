@@ -0,0 +1,80 @@
+use super::{Addr, Error, Machine};
+use std::borrow::Cow;
+use std::fmt;
+
+/// A named memory write applied to a program before it starts running,
+/// such as inserting quarters to enable an arcade's free play mode.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Patch {
+    pub name: Cow<'static, str>,
+    pub addr: Addr,
+    pub value: i64,
+    /// Value the address must hold before patching, when known
+    pub expected: Option<i64>,
+}
+
+impl Patch {
+    pub fn new<S: Into<Cow<'static, str>>>(name: S, addr: Addr, value: i64) -> Patch {
+        Patch {
+            name: name.into(),
+            addr,
+            value,
+            expected: None,
+        }
+    }
+
+    /// Require the patched address to hold `old` before the patch applies.
+    pub fn expecting(mut self, old: i64) -> Patch {
+        self.expected = Some(old);
+        self
+    }
+
+    /// Check the patch against a machine's memory and write the new value.
+    /// Patches may only overwrite memory that was part of the loaded program.
+    /// Only `Machine::with_patches` applies patches, so each one is logged.
+    fn apply(&self, machine: &mut Machine) -> Result<(), Error> {
+        if machine.memory.len() <= self.addr.0 {
+            return Err(Error::BadAddress);
+        }
+        if let Some(old) = self.expected {
+            if machine[self.addr] != old {
+                return Err(Error::PatchMismatch);
+            }
+        }
+        machine[self.addr] = self.value;
+        Ok(())
+    }
+}
+
+/// Renders in the program file's `@patch` directive syntax
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}={}", self.name, self.addr.0, self.value)?;
+        if let Some(old) = self.expected {
+            write!(f, " was={}", old)?;
+        }
+        Ok(())
+    }
+}
+
+impl Machine {
+    /// Apply a sequence of patches in order, recording each one in the
+    /// machine's patch log. The log is shared between clones of a machine.
+    pub fn with_patches<I>(mut self, patches: I) -> Result<Machine, Error>
+    where
+        I: IntoIterator<Item = Patch>,
+    {
+        let mut log = self.patches.to_vec();
+        for patch in patches {
+            patch.apply(&mut self)?;
+            log.push(patch);
+        }
+        self.patches = log.into();
+        Ok(self)
+    }
+
+    /// Patches applied to this machine, in application order
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+}
@@ -0,0 +1,475 @@
+//! Intcode program files.
+//!
+//! The text format extends the puzzle's comma-separated listing with
+//! comments, free-form separators and a metadata header:
+//!
+//! ```text
+//! # Day 13 arcade cabinet
+//! @name breakout
+//! @protocol numeric
+//! @patch free-play 0=2 was=1
+//! 1,380,379,385,
+//! 1008 2151 381 383
+//! ```
+//!
+//! Comments run from `#` to the end of the line. Words may be separated
+//! by any mix of commas and whitespace. Directives start with `@` and must
+//! appear before the first word of code. A name runs to the end of its
+//! line and may contain spaces; `\#` stands for a `#` that doesn't start a
+//! comment. Patches are named and may give the value the address must hold
+//! before patching with `was=`.
+//!
+//! The binary format is a magic number followed by the header text and
+//! the code words, all lengths and words encoded as zigzag LEB128 varints.
+
+use super::{Addr, Error, Machine, Patch};
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// Magic number at the start of binary encoded programs
+pub const MAGIC: &[u8; 4] = b"ICP\x01";
+
+/// Input/output convention a program expects
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Protocol {
+    /// Inputs and outputs are plain integers
+    Numeric,
+    /// Inputs and outputs are ASCII characters
+    Ascii,
+}
+
+impl Protocol {
+    fn name(self) -> &'static str {
+        match self {
+            Protocol::Numeric => "numeric",
+            Protocol::Ascii => "ascii",
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = LoadErrorKind;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "numeric" => Ok(Protocol::Numeric),
+            "ascii" => Ok(Protocol::Ascii),
+            _ => Err(LoadErrorKind::UnknownProtocol(s.to_string())),
+        }
+    }
+}
+
+/// An intcode program along with its optional metadata
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Program {
+    pub name: Option<String>,
+    pub protocol: Option<Protocol>,
+    /// Memory writes applied before the machine starts
+    pub patches: Vec<Patch>,
+    pub code: Vec<i64>,
+}
+
+/// Where in the input a load error occurred
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Location {
+    /// 1-based line and column of the offending token
+    Text { line: usize, column: usize },
+    /// 0-based offset into a binary encoding
+    Byte(usize),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LoadErrorKind {
+    BadNumber(ParseIntError),
+    BadAddress,
+    UnknownDirective(String),
+    UnknownProtocol(String),
+    MissingArgument,
+    ExtraArgument,
+    DirectiveAfterCode,
+    BadPatch,
+    BadMagic,
+    BadUtf8,
+    Truncated,
+    Overflow,
+    TrailingBytes,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LoadError {
+    pub location: Location,
+    pub kind: LoadErrorKind,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Text { line, column } => write!(f, "line {}, column {}", line, column),
+            Location::Byte(offset) => write!(f, "byte {}", offset),
+        }
+    }
+}
+
+impl fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadErrorKind::BadNumber(e) => write!(f, "bad number: {}", e),
+            LoadErrorKind::BadAddress => f.write_str("bad patch address"),
+            LoadErrorKind::UnknownDirective(d) => write!(f, "unknown directive @{}", d),
+            LoadErrorKind::UnknownProtocol(p) => write!(f, "unknown protocol {}", p),
+            LoadErrorKind::MissingArgument => f.write_str("missing directive argument"),
+            LoadErrorKind::ExtraArgument => f.write_str("unexpected directive argument"),
+            LoadErrorKind::DirectiveAfterCode => f.write_str("directive after start of code"),
+            LoadErrorKind::BadPatch => f.write_str("patch is not of the form addr=value"),
+            LoadErrorKind::BadMagic => f.write_str("missing magic number"),
+            LoadErrorKind::BadUtf8 => f.write_str("invalid UTF-8"),
+            LoadErrorKind::Truncated => f.write_str("unexpected end of input"),
+            LoadErrorKind::Overflow => f.write_str("varint overflow"),
+            LoadErrorKind::TrailingBytes => f.write_str("trailing bytes after code"),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl Program {
+    /// Load a program from either encoding, detected by the magic number.
+    pub fn load(bytes: &[u8]) -> Result<Program, LoadError> {
+        if bytes.starts_with(MAGIC) {
+            Program::decode(bytes)
+        } else {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => text.parse(),
+                Err(e) => Err(LoadError {
+                    location: Location::Byte(e.valid_up_to()),
+                    kind: LoadErrorKind::BadUtf8,
+                }),
+            }
+        }
+    }
+
+    /// Look up one of the program's patches by name
+    pub fn patch(&self, name: &str) -> Option<&Patch> {
+        self.patches.iter().find(|p| p.name == name)
+    }
+
+    /// Construct a machine running the unpatched program
+    pub fn machine(&self) -> Machine {
+        Machine::new(self.code.clone())
+    }
+
+    /// Construct a machine with all of the program's patches applied
+    pub fn patched_machine(&self) -> Result<Machine, Error> {
+        self.machine().with_patches(self.patches.iter().cloned())
+    }
+
+    /// Render only the metadata directives of the text format
+    fn header(&self) -> String {
+        let mut out = String::new();
+        if let Some(name) = &self.name {
+            out += &format!("@name {}\n", name.replace('#', "\\#"));
+        }
+        if let Some(protocol) = self.protocol {
+            out += &format!("@protocol {}\n", protocol.name());
+        }
+        for patch in &self.patches {
+            out += &format!("@patch {}\n", patch);
+        }
+        out
+    }
+
+    /// Encode the program in the compact binary format
+    pub fn encode(&self) -> Vec<u8> {
+        let header = self.header();
+        let mut out = MAGIC.to_vec();
+        write_varint(&mut out, header.len() as u64);
+        out.extend_from_slice(header.as_bytes());
+        write_varint(&mut out, self.code.len() as u64);
+        for &w in &self.code {
+            write_varint(&mut out, zigzag(w));
+        }
+        out
+    }
+
+    /// Decode a program from the compact binary format
+    pub fn decode(bytes: &[u8]) -> Result<Program, LoadError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(LoadError { location: Location::Byte(0), kind: LoadErrorKind::BadMagic });
+        }
+        let mut reader = Reader { bytes, offset: MAGIC.len() };
+
+        let header_len = reader.len()?;
+        let start = reader.offset;
+        let header = reader.take(header_len)?;
+        let header = std::str::from_utf8(header).map_err(|e| LoadError {
+            location: Location::Byte(start + e.valid_up_to()),
+            kind: LoadErrorKind::BadUtf8,
+        })?;
+        let mut program: Program = header.parse()?;
+
+        let count = reader.len()?;
+        program.code = Vec::with_capacity(count.min(bytes.len()));
+        for _ in 0..count {
+            program.code.push(unzigzag(reader.varint()?));
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(reader.error(LoadErrorKind::TrailingBytes));
+        }
+        Ok(program)
+    }
+}
+
+impl FromStr for Program {
+    type Err = LoadError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut program = Program::default();
+
+        for (line_no, line) in input.lines().enumerate() {
+            let line = strip_comment(line);
+            let mut tokens = tokens(line).map(|(start, tok)| {
+                let column = line[..start].chars().count() + 1;
+                (Location::Text { line: line_no + 1, column }, start, tok)
+            });
+
+            while let Some((location, _, token)) = tokens.next() {
+                let err = |kind| LoadError { location, kind };
+
+                if let Some(directive) = token.strip_prefix('@') {
+                    if !program.code.is_empty() {
+                        return Err(err(LoadErrorKind::DirectiveAfterCode));
+                    }
+                    let (arg_loc, arg_start, arg) = tokens
+                        .next()
+                        .ok_or_else(|| err(LoadErrorKind::MissingArgument))?;
+                    let arg_err = |kind| LoadError { location: arg_loc, kind };
+                    match directive {
+                        "name" => {
+                            program.name = Some(line[arg_start..].trim_end().replace("\\#", "#"));
+                            break;
+                        }
+                        "protocol" => program.protocol = Some(arg.parse().map_err(arg_err)?),
+                        "patch" => {
+                            let (loc, _, write) = tokens
+                                .next()
+                                .ok_or_else(|| err(LoadErrorKind::MissingArgument))?;
+                            let (addr, value) = parse_patch(write)
+                                .map_err(|kind| LoadError { location: loc, kind })?;
+                            let mut patch = Patch::new(arg.to_string(), addr, value);
+                            if let Some((loc, _, was)) = tokens.next() {
+                                let old = parse_expected(was)
+                                    .map_err(|kind| LoadError { location: loc, kind })?;
+                                patch = patch.expecting(old);
+                            }
+                            program.patches.push(patch)
+                        }
+                        _ => return Err(err(LoadErrorKind::UnknownDirective(directive.to_string()))),
+                    }
+                    if let Some((location, _, _)) = tokens.next() {
+                        return Err(LoadError { location, kind: LoadErrorKind::ExtraArgument });
+                    }
+                } else {
+                    let word = i64::from_str(token).map_err(|e| err(LoadErrorKind::BadNumber(e)))?;
+                    program.code.push(word);
+                }
+            }
+        }
+
+        Ok(program)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.header())?;
+        let words: Vec<String> = self.code.iter().map(i64::to_string).collect();
+        writeln!(f, "{}", words.join(","))
+    }
+}
+
+/// The part of a line before its comment, which starts at the first `#`
+/// that isn't escaped as `\#`
+fn strip_comment(line: &str) -> &str {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if c == '#' && !escaped {
+            return &line[..i];
+        }
+        escaped = c == '\\';
+    }
+    line
+}
+
+/// Split a line into tokens with their starting byte offsets
+fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_sep = |c: char| c == ',' || c.is_whitespace();
+    let mut rest = line.char_indices().peekable();
+    std::iter::from_fn(move || {
+        while rest.next_if(|&(_, c)| is_sep(c)).is_some() {}
+        let &(start, _) = rest.peek()?;
+        let mut end = line.len();
+        while let Some(&(i, c)) = rest.peek() {
+            if is_sep(c) {
+                end = i;
+                break;
+            }
+            rest.next();
+        }
+        Some((start, &line[start..end]))
+    })
+}
+
+fn parse_patch(arg: &str) -> Result<(Addr, i64), LoadErrorKind> {
+    let mut parts = arg.splitn(2, '=');
+    let addr = parts.next().unwrap_or("");
+    let value = parts.next().ok_or(LoadErrorKind::BadPatch)?;
+    let addr = usize::from_str(addr).map_err(|_| LoadErrorKind::BadAddress)?;
+    let value = i64::from_str(value).map_err(LoadErrorKind::BadNumber)?;
+    Ok((Addr(addr), value))
+}
+
+fn parse_expected(arg: &str) -> Result<i64, LoadErrorKind> {
+    let old = arg.strip_prefix("was=").ok_or(LoadErrorKind::BadPatch)?;
+    i64::from_str(old).map_err(LoadErrorKind::BadNumber)
+}
+
+fn zigzag(x: i64) -> u64 {
+    ((x << 1) ^ (x >> 63)) as u64
+}
+
+fn unzigzag(x: u64) -> i64 {
+    (x >> 1) as i64 ^ -((x & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        out.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, kind: LoadErrorKind) -> LoadError {
+        LoadError { location: Location::Byte(self.offset), kind }
+    }
+
+    fn varint(&mut self) -> Result<u64, LoadError> {
+        let start = self.offset;
+        let mut result = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.offset).ok_or_else(|| self.error(LoadErrorKind::Truncated))?;
+            self.offset += 1;
+            let bits = u64::from(byte & 0x7f);
+            if bits << shift >> shift != bits {
+                break;
+            }
+            result |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        Err(LoadError { location: Location::Byte(start), kind: LoadErrorKind::Overflow })
+    }
+
+    fn len(&mut self) -> Result<usize, LoadError> {
+        let start = self.offset;
+        let n = self.varint()?;
+        usize::try_from(n).map_err(|_| LoadError { location: Location::Byte(start), kind: LoadErrorKind::Overflow })
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadError> {
+        let end = self.offset.checked_add(n).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| self.error(LoadErrorKind::Truncated))?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANNOTATED: &str = "\
+# quine from day 9
+@name quine
+@protocol numeric
+@patch start 0=109 was=109
+
+109, 1, 204, -1,   # output
+1001 100 1 100
+1008,100,16,101,1006,101,0,99
+";
+
+    #[test]
+    fn parse_annotated() {
+        let p: Program = ANNOTATED.parse().unwrap();
+        assert_eq!(p.name.as_deref(), Some("quine"));
+        assert_eq!(p.protocol, Some(Protocol::Numeric));
+        assert_eq!(p.patches, vec![Patch::new("start", Addr(0), 109).expecting(109)]);
+        assert_eq!(p.patched_machine().unwrap().patches(), &p.patches[..]);
+        assert_eq!(
+            p.code,
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99]
+        );
+        assert_eq!(p.to_string().parse::<Program>().unwrap(), p);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let p: Program = ANNOTATED.parse().unwrap();
+        let bytes = p.encode();
+        assert!(bytes.len() < ANNOTATED.len());
+        assert_eq!(Program::load(&bytes).unwrap(), p);
+        assert_eq!(unzigzag(zigzag(i64::MIN)), i64::MIN);
+        assert_eq!(unzigzag(zigzag(i64::MAX)), i64::MAX);
+
+        let err = Program::decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.kind, LoadErrorKind::Truncated);
+    }
+
+    #[test]
+    fn error_positions() {
+        let err = "1,2,3\n4, x5,6".parse::<Program>().unwrap_err();
+        assert_eq!(err.location, Location::Text { line: 2, column: 4 });
+        assert!(matches!(err.kind, LoadErrorKind::BadNumber(_)));
+
+        let err = "1\n@name late".parse::<Program>().unwrap_err();
+        assert_eq!(err.location, Location::Text { line: 2, column: 1 });
+        assert_eq!(err.kind, LoadErrorKind::DirectiveAfterCode);
+
+        let err = "@patch free-play 0:2".parse::<Program>().unwrap_err();
+        assert_eq!(err.location, Location::Text { line: 1, column: 18 });
+        assert_eq!(err.kind, LoadErrorKind::BadPatch);
+
+        let err = "@patch free-play 0=2 1".parse::<Program>().unwrap_err();
+        assert_eq!(err.location, Location::Text { line: 1, column: 22 });
+        assert_eq!(err.kind, LoadErrorKind::BadPatch);
+        assert_eq!(err.to_string(), "line 1, column 22: patch is not of the form addr=value");
+    }
+
+    #[test]
+    fn name_with_spaces() {
+        let p: Program = "@name  Springdroid Adventure  # day 21\n99".parse().unwrap();
+        assert_eq!(p.name.as_deref(), Some("Springdroid Adventure"));
+        assert_eq!(p.to_string().parse::<Program>().unwrap(), p);
+
+        let p: Program = "@name Room \\#3 # comment\n99".parse().unwrap();
+        assert_eq!(p.name.as_deref(), Some("Room #3"));
+        assert_eq!(p.to_string().parse::<Program>().unwrap(), p);
+        assert_eq!(Program::load(&p.encode()).unwrap(), p);
+    }
+}
@@ -0,0 +1,117 @@
+//! Brute-force searches over machine inputs spread across threads.
+//!
+//! Every evaluation receives its own fork of a base machine, so setup
+//! work (like feeding a network address or patching memory) only needs
+//! to happen once before the search begins.
+
+use super::Machine;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+fn worker_count(jobs: usize) -> usize {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    cores.min(jobs).max(1)
+}
+
+/// Find the result for the earliest parameter (in slice order) for which
+/// `f` returns `Some`. Workers stop claiming parameters past the best
+/// match seen so far, so the answer does not depend on thread timing.
+pub fn find<P, T, F>(base: &Machine, params: &[P], f: F) -> Option<T>
+where
+    P: Sync,
+    T: Send,
+    F: Fn(Machine, &P) -> Option<T> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let best_index = AtomicUsize::new(usize::MAX);
+    let best: Mutex<Option<(usize, T)>> = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for _ in 0..worker_count(params.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= params.len() || i > best_index.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(t) = f(base.clone(), &params[i]) {
+                    best_index.fetch_min(i, Ordering::Relaxed);
+                    let mut best = best.lock().unwrap();
+                    if best.as_ref().is_none_or(|&(j, _)| i < j) {
+                        *best = Some((i, t));
+                    }
+                }
+            });
+        }
+    });
+
+    best.into_inner().unwrap().map(|(_, t)| t)
+}
+
+/// Evaluate `f` for every parameter, returning results in parameter order.
+pub fn map<P, T, F>(base: &Machine, params: &[P], f: F) -> Vec<T>
+where
+    P: Sync,
+    T: Send,
+    F: Fn(Machine, &P) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, T)>> = Mutex::new(Vec::with_capacity(params.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..worker_count(params.len()) {
+            scope.spawn(|| {
+                let mut local = vec![];
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= params.len() {
+                        break;
+                    }
+                    local.push((i, f(base.clone(), &params[i])));
+                }
+                results.lock().unwrap().extend(local);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, t)| t).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Step;
+
+    // Reads one input and outputs its square
+    const SQUARE: [i64; 7] = [3, 0, 2, 0, 0, 0, 4];
+
+    fn square(mut m: Machine, &x: &i64) -> i64 {
+        let i = m.step().unwrap().input().unwrap();
+        m[i] = x;
+        match m.step().unwrap() {
+            Step::Output(o) => o,
+            s => panic!("unexpected step {:?}", s),
+        }
+    }
+
+    #[test]
+    fn find_is_deterministic() {
+        let m = Machine::new(SQUARE.to_vec());
+        let params: Vec<i64> = (-50..50).collect();
+        let found = find(&m, &params, |m, x| Some(*x).filter(|_| square(m, x) > 100));
+        assert_eq!(found, Some(-50));
+        let found = find(&m, &params, |m, x| Some(*x).filter(|&x| x > 0 && square(m, &x) > 100));
+        assert_eq!(found, Some(11));
+        assert_eq!(find(&m, &params, |m, x| Some(*x).filter(|_| square(m, x) < 0)), None);
+    }
+
+    #[test]
+    fn map_preserves_order() {
+        let m = Machine::new(SQUARE.to_vec());
+        let params: Vec<i64> = (0..100).collect();
+        let expected: Vec<i64> = params.iter().map(|x| x * x).collect();
+        assert_eq!(map(&m, &params, square), expected);
+    }
+}
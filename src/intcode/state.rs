@@ -0,0 +1,185 @@
+//! Machine state identity.
+//!
+//! Two machines are equal when their program counters, relative bases
+//! and memories agree, where memory is compared ignoring trailing zeros
+//! (unallocated memory reads as zero anyway). The applied patch log is
+//! not part of the state.
+//!
+//! Hashing memory from scratch costs a pass over every cell, so a machine
+//! can keep a rolling hash: the wrapping sum of a mixed value for every
+//! nonzero cell. A write subtracts the old cell's contribution and adds
+//! the new one, so hashing the machine costs O(1). Machines that are never
+//! hashed shouldn't pay for that on every write, so tracking starts only
+//! with `Machine::with_state_tracking` or `Machine::step_checked`.
+
+use super::{Error, Machine, Step};
+use std::hash::{Hash, Hasher};
+
+fn mix(mut x: u64) -> u64 {
+    // splitmix64 finalizer
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn contribution(idx: usize, value: i64) -> u64 {
+    if value == 0 {
+        0
+    } else {
+        mix(mix(idx as u64) ^ value as u64)
+    }
+}
+
+pub(super) fn memory_hash(memory: &[i64]) -> u64 {
+    memory
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, &v)| acc.wrapping_add(contribution(i, v)))
+}
+
+impl Machine {
+    /// Keep a rolling hash of memory from now on, so that hashing and
+    /// comparing this machine and its clones is cheap
+    pub fn with_state_tracking(mut self) -> Machine {
+        self.track_state();
+        self
+    }
+
+    fn track_state(&mut self) {
+        if self.memory_hash.is_none() {
+            self.memory_hash = Some(memory_hash(&self.memory));
+            self.pending_write = None;
+        }
+    }
+
+    /// Called whenever a memory cell is mutably borrowed. The new value is
+    /// not known yet, so the cell's contribution is removed now and added
+    /// back once it is no longer the most recent write.
+    pub(super) fn track_write(&mut self, idx: usize) {
+        if let Some(hash) = self.memory_hash {
+            let hash = hash.wrapping_add(self.pending_contribution());
+            self.memory_hash = Some(hash.wrapping_sub(contribution(idx, self.memory[idx])));
+            self.pending_write = Some(idx);
+        }
+    }
+
+    fn pending_contribution(&self) -> u64 {
+        self.pending_write
+            .map_or(0, |idx| contribution(idx, self.memory[idx]))
+    }
+
+    /// Hash of the nonzero memory cells, from the rolling hash if tracked
+    fn current_memory_hash(&self) -> u64 {
+        match self.memory_hash {
+            Some(hash) => hash.wrapping_add(self.pending_contribution()),
+            None => memory_hash(&self.memory),
+        }
+    }
+
+    /// Hash of the machine's full state, computed in constant time once
+    /// state tracking is enabled
+    pub fn state_hash(&self) -> u64 {
+        let memory = self.current_memory_hash();
+        mix(memory ^ mix(self.pc as u64 ^ mix(self.rel_base as u64)))
+    }
+
+    fn trimmed_memory(&self) -> &[i64] {
+        let len = self.memory.iter().rposition(|&x| x != 0).map_or(0, |i| i + 1);
+        &self.memory[..len]
+    }
+
+    /// Run until the machine halts or needs to perform I/O like `step`,
+    /// but fail with `Error::InfiniteLoop` if the machine revisits an
+    /// earlier state first. Such a machine would never produce another
+    /// event.
+    ///
+    /// Uses Brent's algorithm, so only one snapshot is kept at a time and
+    /// loops are found within a small multiple of their length. Enables
+    /// state tracking, since the snapshot is compared after every step.
+    pub fn step_checked(&mut self) -> Result<Step, Error> {
+        self.track_state();
+        let mut snapshot: Option<Machine> = None;
+        let mut power: u64 = 64;
+        let mut lambda: u64 = 0;
+        loop {
+            if let Some(step) = self.step_instruction()? {
+                return Ok(step);
+            }
+            if snapshot.as_ref() == Some(self) {
+                return Err(Error::InfiniteLoop);
+            }
+            lambda += 1;
+            if lambda == power {
+                snapshot = Some(self.clone());
+                power *= 2;
+                lambda = 0;
+            }
+        }
+    }
+}
+
+impl PartialEq for Machine {
+    fn eq(&self, other: &Self) -> bool {
+        // rolling hashes rule out most unequal memories without a pass over
+        // memory, but hashing an untracked machine would cost just as much
+        let tracked = self.memory_hash.is_some() && other.memory_hash.is_some();
+        self.pc == other.pc
+            && self.rel_base == other.rel_base
+            && (!tracked || self.state_hash() == other.state_hash())
+            && self.trimmed_memory() == other.trimmed_memory()
+    }
+}
+
+impl Eq for Machine {}
+
+impl Hash for Machine {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.state_hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Addr;
+    use std::collections::HashSet;
+
+    #[test]
+    fn normalized_equality() {
+        let a = Machine::new(vec![1, 2, 3]);
+        let mut b = Machine::new(vec![1, 2, 3, 0, 0]).with_state_tracking();
+        assert_eq!(a, b);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        b[Addr(1000)] = 7;
+        assert_ne!(a, b);
+        b[Addr(1000)] = 0;
+        assert_eq!(a, b);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        let set: HashSet<Machine> = vec![a, b].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn rolling_hash_matches_fresh_hash() {
+        let quine = vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+        let mut m = Machine::new(quine).with_state_tracking();
+        while m.step().unwrap() != Step::Halt {
+            assert!(m.memory_hash.is_some());
+            assert_eq!(m.current_memory_hash(), memory_hash(&m.memory));
+        }
+    }
+
+    #[test]
+    fn detects_infinite_loop() {
+        // outputs 1, then jumps to itself forever
+        let mut m = Machine::new(vec![104, 1, 1105, 1, 2]);
+        assert_eq!(m.step_checked(), Ok(Step::Output(1)));
+        assert_eq!(m.step_checked(), Err(Error::InfiniteLoop));
+
+        // counts down from 500 before halting
+        let mut m = Machine::new(vec![1001, 9, -1, 9, 1005, 9, 0, 99, 0, 500]);
+        assert_eq!(m.step_checked(), Ok(Step::Halt));
+    }
+}
@@ -1,6 +1,14 @@
 pub mod bfs;
+pub mod bitgrid;
+pub mod cycle;
+pub mod graph;
+pub mod grid;
+pub mod hex;
+pub mod point;
 pub mod pos;
+pub mod search;
 pub mod intcode;
+pub mod memo;
 use std::fs;
 
 pub fn load_input_file(day: u64) -> String {
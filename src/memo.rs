@@ -0,0 +1,170 @@
+//! Memoized recursion over hashable states.
+//!
+//! A recursive function is written as a closure that receives a `Recur`
+//! handle for making its recursive calls:
+//!
+//! ```
+//! use advent::memo::Memo;
+//!
+//! let mut memo = Memo::new();
+//! let fib = memo.get(80u64, |r, &n| {
+//!     Ok(if n < 2 { n } else { r.get(n - 1)? + r.get(n - 2)? })
+//! });
+//! assert_eq!(fib, Ok(23416728348467685));
+//! ```
+//!
+//! Results are cached across calls to `get` on the same table, so a table
+//! must only ever be used with one function.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Error returned when computing a key requires its own value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cycle<K>(pub K);
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Stats {
+    /// Lookups answered from the table
+    pub hits: u64,
+    /// Lookups that ran the function
+    pub misses: u64,
+}
+
+impl Stats {
+    /// Fraction of lookups answered from the table
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Memo<K, V> {
+    table: HashMap<K, V>,
+    in_progress: HashSet<K>,
+    stats: Stats,
+}
+
+type RecurFn<'a, K, V> = dyn Fn(&mut Recur<K, V>, &K) -> Result<V, Cycle<K>> + 'a;
+
+/// Handle for making recursive calls through a memo table
+pub struct Recur<'a, K, V> {
+    memo: &'a mut Memo<K, V>,
+    f: &'a RecurFn<'a, K, V>,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Memo {
+            table: HashMap::new(),
+            in_progress: HashSet::new(),
+            stats: Stats::default(),
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Memo<K, V> {
+    pub fn new() -> Self {
+        Memo::default()
+    }
+
+    /// Look up `key`, computing it with `f` if it isn't in the table yet.
+    pub fn get<F>(&mut self, key: K, f: F) -> Result<V, Cycle<K>>
+    where
+        F: Fn(&mut Recur<K, V>, &K) -> Result<V, Cycle<K>>,
+    {
+        Recur { memo: self, f: &f }.get(key)
+    }
+
+    /// Previously computed value for `key`, without running anything
+    pub fn cached(&self, key: &K) -> Option<&V> {
+        self.table.get(key)
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+impl<'a, K: Clone + Eq + Hash, V: Clone> Recur<'a, K, V> {
+    /// Recursively compute the value for `key`. Fails with `Cycle` when
+    /// `key` is already being computed further up the call stack.
+    pub fn get(&mut self, key: K) -> Result<V, Cycle<K>> {
+        if let Some(v) = self.memo.table.get(&key) {
+            self.memo.stats.hits += 1;
+            return Ok(v.clone());
+        }
+        if !self.memo.in_progress.insert(key.clone()) {
+            return Err(Cycle(key));
+        }
+        self.memo.stats.misses += 1;
+
+        let f = self.f;
+        let result = f(self, &key);
+        self.memo.in_progress.remove(&key);
+        if let Ok(v) = &result {
+            self.memo.table.insert(key, v.clone());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fibonacci_stats() {
+        let mut memo = Memo::new();
+        let fib = |r: &mut Recur<u64, u64>, &n: &u64| Ok(if n < 2 { n } else { r.get(n - 1)? + r.get(n - 2)? });
+        assert_eq!(memo.get(30, fib), Ok(832040));
+        assert_eq!(memo.len(), 31);
+        assert_eq!(memo.stats(), Stats { hits: 28, misses: 31 });
+
+        assert_eq!(memo.get(30, fib), Ok(832040));
+        assert_eq!(memo.stats().hits, 29);
+        assert!(memo.stats().hit_rate() > 0.45);
+    }
+
+    #[test]
+    fn detects_cycles() {
+        // each chemical needs the ones listed after it
+        let recipes: HashMap<&'static str, Vec<&'static str>> = vec![
+            ("FUEL", vec!["A", "B"]),
+            ("A", vec!["ORE"]),
+            ("B", vec!["C"]),
+            ("C", vec!["B"]),
+            ("ORE", vec![]),
+        ]
+        .into_iter()
+        .collect();
+        let depth = |r: &mut Recur<&'static str, usize>, k: &&'static str| {
+            let mut d = 0;
+            for &input in &recipes[k] {
+                d = d.max(1 + r.get(input)?);
+            }
+            Ok(d)
+        };
+
+        let mut memo = Memo::new();
+        assert_eq!(memo.get("A", depth), Ok(1));
+        assert_eq!(memo.get("FUEL", depth), Err(Cycle("B")));
+        assert_eq!(memo.cached(&"A"), Some(&1));
+        assert_eq!(memo.cached(&"FUEL"), None);
+        // a failed computation leaves nothing marked as in progress
+        assert_eq!(memo.get("C", depth), Err(Cycle("C")));
+    }
+}
@@ -0,0 +1,161 @@
+//! Integer points with any number of dimensions, for puzzles that outgrow
+//! the two dimensions of `Pos`.
+
+use crate::pos::Pos;
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign};
+
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Point<const N: usize>(pub [i64; N]);
+
+impl<const N: usize> Point<N> {
+    pub const ORIGIN: Point<N> = Point([0; N]);
+
+    /// Unit vector along an axis
+    pub fn unit(axis: usize) -> Point<N> {
+        let mut p = Point::ORIGIN;
+        p[axis] = 1;
+        p
+    }
+
+    /// Manhattan distance from the origin
+    pub fn norm1(self) -> i64 {
+        self.0.iter().map(|c| c.abs()).sum()
+    }
+
+    /// Chebyshev distance from the origin
+    pub fn norm_inf(self) -> i64 {
+        self.0.iter().map(|c| c.abs()).max().unwrap_or(0)
+    }
+
+    /// Sign of each coordinate
+    pub fn signum(self) -> Point<N> {
+        Point(self.0.map(i64::signum))
+    }
+
+    /// The 2N points one step along a single axis
+    pub fn orthogonal_neighbors(self) -> impl Iterator<Item = Point<N>> {
+        (0..N).flat_map(move |axis| {
+            let unit = Point::unit(axis);
+            [self - unit, self + unit]
+        })
+    }
+
+    /// The 3^N - 1 points that differ by at most one in every coordinate
+    pub fn neighbors(self) -> impl Iterator<Item = Point<N>> {
+        (0..3usize.pow(N as u32))
+            .map(move |mut code| {
+                let mut p = self;
+                for c in &mut p.0 {
+                    *c += (code % 3) as i64 - 1;
+                    code /= 3;
+                }
+                p
+            })
+            .filter(move |&p| p != self)
+    }
+}
+
+impl From<Pos> for Point<2> {
+    fn from(p: Pos) -> Self {
+        Point([p.x, p.y])
+    }
+}
+
+impl From<Point<2>> for Pos {
+    fn from(Point([x, y]): Point<2>) -> Self {
+        Pos { x, y }
+    }
+}
+
+impl<const N: usize> Index<usize> for Point<N> {
+    type Output = i64;
+    fn index(&self, axis: usize) -> &i64 {
+        &self.0[axis]
+    }
+}
+
+impl<const N: usize> IndexMut<usize> for Point<N> {
+    fn index_mut(&mut self, axis: usize) -> &mut i64 {
+        &mut self.0[axis]
+    }
+}
+
+impl<const N: usize> Add for Point<N> {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl<const N: usize> Sub for Point<N> {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl<const N: usize> AddAssign for Point<N> {
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a += b
+        }
+    }
+}
+
+impl<const N: usize> SubAssign for Point<N> {
+    fn sub_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a -= b
+        }
+    }
+}
+
+impl<const N: usize> Neg for Point<N> {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Point(self.0.map(|c| -c))
+    }
+}
+
+impl<const N: usize> Mul<i64> for Point<N> {
+    type Output = Self;
+    fn mul(self, rhs: i64) -> Self::Output {
+        Point(self.0.map(|c| c * rhs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn arithmetic_and_norms() {
+        let p = Point([1, -2, 3]);
+        let q = Point([4, 0, -1]);
+        assert_eq!(p + q, Point([5, -2, 2]));
+        assert_eq!(p - q, Point([-3, -2, 4]));
+        assert_eq!(-p * 2, Point([-2, 4, -6]));
+        assert_eq!((p - q).signum(), Point([-1, -1, 1]));
+        assert_eq!(p.norm1(), 6);
+        assert_eq!(p.norm_inf(), 3);
+        assert_eq!(Point::<0>::ORIGIN.norm_inf(), 0);
+
+        let pos = Pos { x: 3, y: -4 };
+        assert_eq!(Pos::from(Point::from(pos)), pos);
+    }
+
+    #[test]
+    fn neighbor_counts() {
+        let p = Point([5, 5, 5, 5]);
+        let orthogonal: HashSet<Point<4>> = p.orthogonal_neighbors().collect();
+        let all: HashSet<Point<4>> = p.neighbors().collect();
+        assert_eq!(orthogonal.len(), 8);
+        assert_eq!(all.len(), 80);
+        assert!(orthogonal.is_subset(&all));
+        assert!(orthogonal.iter().all(|&q| (q - p).norm1() == 1));
+        assert!(all.iter().all(|&q| (q - p).norm_inf() == 1));
+    }
+}
@@ -1,4 +1,11 @@
+use gcd::Gcd;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::TryFromIntError;
 use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::str::FromStr;
 
 #[derive(Hash, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Pos {
@@ -8,6 +15,64 @@ pub struct Pos {
 
 impl Pos {
     pub const ORIGIN: Pos = Pos { x: 0, y: 0 };
+
+    /// Position of a column and row index, failing if either index is too
+    /// large for an `i64`
+    pub fn from_indices(x: usize, y: usize) -> Result<Pos, TryFromIntError> {
+        Ok(Pos {
+            x: i64::try_from(x)?,
+            y: i64::try_from(y)?,
+        })
+    }
+
+    /// `self + rhs`, or `None` on overflow
+    pub fn checked_add(self, rhs: Pos) -> Option<Pos> {
+        Some(Pos {
+            x: self.x.checked_add(rhs.x)?,
+            y: self.y.checked_add(rhs.y)?,
+        })
+    }
+
+    /// `self - rhs`, or `None` on overflow
+    pub fn checked_sub(self, rhs: Pos) -> Option<Pos> {
+        Some(Pos {
+            x: self.x.checked_sub(rhs.x)?,
+            y: self.y.checked_sub(rhs.y)?,
+        })
+    }
+
+    pub fn wrapping_add(self, rhs: Pos) -> Pos {
+        Pos {
+            x: self.x.wrapping_add(rhs.x),
+            y: self.y.wrapping_add(rhs.y),
+        }
+    }
+
+    pub fn wrapping_sub(self, rhs: Pos) -> Pos {
+        Pos {
+            x: self.x.wrapping_sub(rhs.x),
+            y: self.y.wrapping_sub(rhs.y),
+        }
+    }
+
+    /// `advance`, or `None` on overflow
+    pub fn checked_advance(self, dir: Dir, n: i64) -> Option<Pos> {
+        let unit = dir.unit();
+        self.checked_add(Pos {
+            x: unit.x.checked_mul(n)?,
+            y: unit.y.checked_mul(n)?,
+        })
+    }
+
+    /// `advance`, wrapping around at the bounds of `i64`
+    pub fn wrapping_advance(self, dir: Dir, n: i64) -> Pos {
+        let unit = dir.unit();
+        self.wrapping_add(Pos {
+            x: unit.x.wrapping_mul(n),
+            y: unit.y.wrapping_mul(n),
+        })
+    }
+
     pub fn advance(mut self, dir: Dir, n: i64) -> Pos {
         match dir {
             Dir::N => { self.y -= n }
@@ -18,6 +83,15 @@ impl Pos {
         self
     }
 
+    /// Move `n` steps in any direction with a `Pos` unit vector
+    pub fn offset<D: Direction<Vector = Pos>>(self, dir: D, n: i64) -> Pos {
+        let unit = dir.unit();
+        Pos {
+            x: self.x + unit.x * n,
+            y: self.y + unit.y * n,
+        }
+    }
+
     pub fn norm1(self) -> i64 {
         self.x.abs() + self.y.abs()
     }
@@ -81,6 +155,251 @@ impl Dir {
             Dir::W => Dir::E,
         }
     }
+
+    pub fn apply(self, turn: Turn) -> Dir {
+        match turn {
+            Turn::Left => self.turn_counterclockwise(),
+            Turn::Right => self.turn_clockwise(),
+        }
+    }
+
+    /// How this direction is written in an alphabet
+    pub fn symbol(self, alphabet: Alphabet) -> &'static str {
+        let (n, s, e, w) = alphabet.symbols();
+        match self {
+            Dir::N => n,
+            Dir::S => s,
+            Dir::E => e,
+            Dir::W => w,
+        }
+    }
+
+    /// Read a direction written in a particular alphabet
+    pub fn parse_with(s: &str, alphabet: Alphabet) -> Result<Dir, ParseError> {
+        Dir::ELEMS
+            .iter()
+            .copied()
+            .find(|d| d.symbol(alphabet) == s)
+            .ok_or_else(|| ParseError(s.to_string()))
+    }
+
+    /// Movement command of the puzzle's intcode droids
+    pub fn code(self) -> i64 {
+        match self {
+            Dir::N => 1,
+            Dir::S => 2,
+            Dir::W => 3,
+            Dir::E => 4,
+        }
+    }
+}
+
+/// Ways of writing a direction as text
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Alphabet {
+    /// `U`, `D`, `R`, `L`
+    Udlr,
+    /// `N`, `S`, `E`, `W`
+    Compass,
+    /// `^`, `v`, `>`, `<` as drawn on puzzle maps
+    Arrows,
+    /// The droids' movement codes `1`, `2`, `4`, `3`
+    Code,
+}
+
+impl Alphabet {
+    pub const ELEMS: [Alphabet; 4] = [Alphabet::Udlr, Alphabet::Compass, Alphabet::Arrows, Alphabet::Code];
+
+    /// Symbols for north, south, east and west
+    fn symbols(self) -> (&'static str, &'static str, &'static str, &'static str) {
+        match self {
+            Alphabet::Udlr => ("U", "D", "R", "L"),
+            Alphabet::Compass => ("N", "S", "E", "W"),
+            Alphabet::Arrows => ("^", "v", ">", "<"),
+            Alphabet::Code => ("1", "2", "4", "3"),
+        }
+    }
+}
+
+/// Text that isn't a direction or turn
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unrecognized direction {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Accepts a direction in any alphabet; no symbol is in more than one
+impl FromStr for Dir {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Alphabet::ELEMS
+            .iter()
+            .find_map(|&a| Dir::parse_with(s, a).ok())
+            .ok_or_else(|| ParseError(s.to_string()))
+    }
+}
+
+/// Writes compass letters; use `symbol` for other alphabets
+impl fmt::Display for Dir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol(Alphabet::Compass))
+    }
+}
+
+/// A quarter turn relative to the current heading
+#[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Copy, Clone, Hash)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+impl Turn {
+    /// Turn command of the puzzle's intcode painting robot
+    pub fn from_code(code: i64) -> Option<Turn> {
+        match code {
+            0 => Some(Turn::Left),
+            1 => Some(Turn::Right),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Turn {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "L" => Ok(Turn::Left),
+            "R" => Ok(Turn::Right),
+            _ => Err(ParseError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Turn::Left => "L",
+            Turn::Right => "R",
+        })
+    }
+}
+
+/// A set of directions that turn in fixed steps, as used on square and
+/// hexagonal grids
+pub trait Direction: Copy + Eq + 'static {
+    /// Displacement type of a single step
+    type Vector;
+
+    /// Every direction in clockwise order
+    const CLOCKWISE: &'static [Self];
+
+    /// Displacement of one step in this direction
+    fn unit(self) -> Self::Vector;
+
+    /// Position of this direction within `CLOCKWISE`
+    fn index(self) -> usize {
+        Self::CLOCKWISE.iter().position(|&d| d == self).unwrap()
+    }
+
+    /// The next direction clockwise
+    fn turn_right(self) -> Self {
+        let all = Self::CLOCKWISE;
+        all[(self.index() + 1) % all.len()]
+    }
+
+    /// The next direction counterclockwise
+    fn turn_left(self) -> Self {
+        let all = Self::CLOCKWISE;
+        all[(self.index() + all.len() - 1) % all.len()]
+    }
+
+    fn opposite(self) -> Self {
+        let all = Self::CLOCKWISE;
+        all[(self.index() + all.len() / 2) % all.len()]
+    }
+
+    /// Every direction in clockwise order
+    fn iter() -> std::iter::Copied<std::slice::Iter<'static, Self>> {
+        Self::CLOCKWISE.iter().copied()
+    }
+}
+
+impl Direction for Dir {
+    type Vector = Pos;
+    const CLOCKWISE: &'static [Dir] = &[Dir::N, Dir::E, Dir::S, Dir::W];
+
+    fn unit(self) -> Pos {
+        Pos::ORIGIN.advance(self, 1)
+    }
+
+    fn turn_right(self) -> Dir {
+        self.turn_clockwise()
+    }
+
+    fn turn_left(self) -> Dir {
+        self.turn_counterclockwise()
+    }
+
+    fn opposite(self) -> Dir {
+        self.turn_around()
+    }
+}
+
+/// Compass directions including diagonals, for Moore neighborhoods
+#[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Copy, Clone, Hash)]
+pub enum Dir8 {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Direction for Dir8 {
+    type Vector = Pos;
+    const CLOCKWISE: &'static [Dir8] = &[
+        Dir8::N,
+        Dir8::NE,
+        Dir8::E,
+        Dir8::SE,
+        Dir8::S,
+        Dir8::SW,
+        Dir8::W,
+        Dir8::NW,
+    ];
+
+    fn unit(self) -> Pos {
+        let (x, y) = match self {
+            Dir8::N => (0, -1),
+            Dir8::NE => (1, -1),
+            Dir8::E => (1, 0),
+            Dir8::SE => (1, 1),
+            Dir8::S => (0, 1),
+            Dir8::SW => (-1, 1),
+            Dir8::W => (-1, 0),
+            Dir8::NW => (-1, -1),
+        };
+        Pos { x, y }
+    }
+}
+
+impl From<Dir> for Dir8 {
+    fn from(dir: Dir) -> Self {
+        match dir {
+            Dir::N => Dir8::N,
+            Dir::S => Dir8::S,
+            Dir::E => Dir8::E,
+            Dir::W => Dir8::W,
+        }
+    }
 }
 
 impl Add for Pos {
@@ -112,3 +431,654 @@ impl SubAssign for Pos {
         self.y -= rhs.y;
     }
 }
+
+/// Direction of a nonzero displacement, reduced so that every
+/// displacement along the same ray has the same bearing. Bearings are
+/// ordered clockwise starting from `Dir::N`, using exact integer
+/// arithmetic.
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Bearing {
+    dx: i64,
+    dy: i64,
+}
+
+impl Bearing {
+    /// Bearing of a displacement, or `None` for the zero displacement
+    pub fn new(delta: Pos) -> Option<Bearing> {
+        // the divisor is 2^63 when a component is i64::MIN, so reduce in
+        // i128; the reduced components are no larger than the originals
+        let divisor = i128::from(delta.x.unsigned_abs().gcd(delta.y.unsigned_abs()));
+        if divisor == 0 {
+            None
+        } else {
+            Some(Bearing {
+                dx: (i128::from(delta.x) / divisor) as i64,
+                dy: (i128::from(delta.y) / divisor) as i64,
+            })
+        }
+    }
+
+    /// Bearing from one position toward another, if they differ
+    pub fn between(from: Pos, to: Pos) -> Option<Bearing> {
+        Bearing::new(to - from)
+    }
+
+    /// Smallest step along this bearing
+    pub fn step(self) -> Pos {
+        Pos { x: self.dx, y: self.dy }
+    }
+
+    /// 0 from north up to but excluding south, 1 from south back to north
+    fn half(self) -> u8 {
+        if self.dx > 0 || self.dx == 0 && self.dy < 0 { 0 } else { 1 }
+    }
+}
+
+impl Ord for Bearing {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // within a half turn, a positive cross product means `other` is
+        // further clockwise; i128 holds any product of two i64 components
+        let cross = i128::from(self.dx) * i128::from(other.dy) - i128::from(self.dy) * i128::from(other.dx);
+        self.half().cmp(&other.half()).then(0.cmp(&cross))
+    }
+}
+
+impl PartialOrd for Bearing {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Points grouped by their bearing from `origin`, nearest first along
+/// each ray. A point at the origin itself is ignored.
+pub fn rays<I>(origin: Pos, points: I) -> BTreeMap<Bearing, Vec<Pos>>
+where
+    I: IntoIterator<Item = Pos>,
+{
+    let mut rays: BTreeMap<Bearing, Vec<Pos>> = BTreeMap::new();
+    for p in points {
+        if let Some(b) = Bearing::between(origin, p) {
+            rays.entry(b).or_default().push(p);
+        }
+    }
+    for ray in rays.values_mut() {
+        ray.sort_by_key(|&p| (p - origin).norm1());
+    }
+    rays
+}
+
+/// Number of points with an unobstructed line of sight from `origin`
+pub fn visible<I>(origin: Pos, points: I) -> usize
+where
+    I: IntoIterator<Item = Pos>,
+{
+    rays(origin, points).len()
+}
+
+/// Order in which a beam rotating clockwise from north, starting at
+/// `origin` and removing the nearest point it hits on each ray, removes
+/// every point
+pub fn sweep<I>(origin: Pos, points: I) -> Vec<Pos>
+where
+    I: IntoIterator<Item = Pos>,
+{
+    let rays = rays(origin, points);
+    let longest = rays.values().map(Vec::len).max().unwrap_or(0);
+    (0..longest)
+        .flat_map(|round| rays.values().filter_map(move |ray| ray.get(round).copied()))
+        .collect()
+}
+
+/// Nonempty axis-aligned rectangle including both corners
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rect {
+    /// Corner with the smallest coordinates
+    pub min: Pos,
+    /// Corner with the largest coordinates
+    pub max: Pos,
+}
+
+impl Rect {
+    pub fn new(min: Pos, max: Pos) -> Rect {
+        assert!(min.x <= max.x && min.y <= max.y, "{:?} is not below and right of {:?}", max, min);
+        Rect { min, max }
+    }
+
+    /// Rectangle with its top-left corner at `min` and the given size
+    pub fn from_size(min: Pos, width: i64, height: i64) -> Rect {
+        Rect::new(min, Pos { x: min.x + width - 1, y: min.y + height - 1 })
+    }
+
+    /// Smallest rectangle containing every point, if there are any
+    pub fn bounding<I>(points: I) -> Option<Rect>
+    where
+        I: IntoIterator<Item = Pos>,
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Rect::new(first, first), Rect::including))
+    }
+
+    pub fn width(self) -> i64 {
+        self.max.x - self.min.x + 1
+    }
+
+    pub fn height(self) -> i64 {
+        self.max.y - self.min.y + 1
+    }
+
+    pub fn area(self) -> i64 {
+        self.width() * self.height()
+    }
+
+    pub fn contains(self, p: Pos) -> bool {
+        self.min.x <= p.x && p.x <= self.max.x && self.min.y <= p.y && p.y <= self.max.y
+    }
+
+    /// Points in both rectangles
+    pub fn intersection(self, other: Rect) -> Option<Rect> {
+        let min = Pos { x: self.min.x.max(other.min.x), y: self.min.y.max(other.min.y) };
+        let max = Pos { x: self.max.x.min(other.max.x), y: self.max.y.min(other.max.y) };
+        if min.x <= max.x && min.y <= max.y {
+            Some(Rect { min, max })
+        } else {
+            None
+        }
+    }
+
+    /// Smallest rectangle containing this one and a point
+    pub fn including(self, p: Pos) -> Rect {
+        Rect {
+            min: Pos { x: self.min.x.min(p.x), y: self.min.y.min(p.y) },
+            max: Pos { x: self.max.x.max(p.x), y: self.max.y.max(p.y) },
+        }
+    }
+
+    /// Grow by `n` on every side
+    pub fn expand(self, n: i64) -> Rect {
+        let margin = Pos { x: n, y: n };
+        Rect::new(self.min - margin, self.max + margin)
+    }
+
+    /// Contained positions in row-major order
+    pub fn positions(self) -> impl Iterator<Item = Pos> {
+        (self.min.y..=self.max.y).flat_map(move |y| (self.min.x..=self.max.x).map(move |x| Pos { x, y }))
+    }
+}
+
+/// Axis-aligned line segment including both of its endpoints
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Segment {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl Segment {
+    /// Segment between two points on a common row or column
+    pub fn new(start: Pos, end: Pos) -> Segment {
+        assert!(start.x == end.x || start.y == end.y, "segment {:?}-{:?} is not axis-aligned", start, end);
+        Segment { start, end }
+    }
+
+    /// Segment covered by moving `n` steps from `start`
+    pub fn from_move(start: Pos, dir: Dir, n: i64) -> Segment {
+        Segment { start, end: start.advance(dir, n) }
+    }
+
+    /// Number of steps from one end to the other
+    pub fn length(self) -> i64 {
+        (self.end - self.start).norm1()
+    }
+
+    fn min(self) -> Pos {
+        Pos { x: self.start.x.min(self.end.x), y: self.start.y.min(self.end.y) }
+    }
+
+    fn max(self) -> Pos {
+        Pos { x: self.start.x.max(self.end.x), y: self.start.y.max(self.end.y) }
+    }
+
+    pub fn contains(self, p: Pos) -> bool {
+        let (lo, hi) = (self.min(), self.max());
+        lo.x <= p.x && p.x <= hi.x && lo.y <= p.y && p.y <= hi.y
+    }
+
+    /// Steps from the start to a point on the segment
+    pub fn steps_to(self, p: Pos) -> Option<i64> {
+        if self.contains(p) {
+            Some((p - self.start).norm1())
+        } else {
+            None
+        }
+    }
+
+    /// Points shared with another segment: a single crossing point, the
+    /// overlapping stretch of two collinear segments, or nothing. The
+    /// result runs in increasing coordinate order.
+    pub fn intersection(self, other: Segment) -> Option<Segment> {
+        let (lo1, hi1) = (self.min(), self.max());
+        let (lo2, hi2) = (other.min(), other.max());
+        let lo = Pos { x: lo1.x.max(lo2.x), y: lo1.y.max(lo2.y) };
+        let hi = Pos { x: hi1.x.min(hi2.x), y: hi1.y.min(hi2.y) };
+        if lo.x <= hi.x && lo.y <= hi.y {
+            Some(Segment { start: lo, end: hi })
+        } else {
+            None
+        }
+    }
+
+    /// Every point from the start to the end
+    pub fn points(self) -> impl Iterator<Item = Pos> {
+        let step = Pos {
+            x: (self.end.x - self.start.x).signum(),
+            y: (self.end.y - self.start.y).signum(),
+        };
+        (0..=self.length()).map(move |i| Pos {
+            x: self.start.x + step.x * i,
+            y: self.start.y + step.y * i,
+        })
+    }
+}
+
+/// Connected sequence of axis-aligned segments, such as a wire
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Path {
+    /// Each segment paired with the steps taken before reaching its start
+    segments: Vec<(Segment, i64)>,
+}
+
+impl Path {
+    /// Path that starts at `start` and makes each move in turn
+    pub fn new<I>(start: Pos, moves: I) -> Path
+    where
+        I: IntoIterator<Item = (Dir, i64)>,
+    {
+        let mut here = start;
+        let mut steps = 0;
+        let mut segments = vec![];
+        for (dir, n) in moves {
+            let segment = Segment::from_move(here, dir, n);
+            segments.push((segment, steps));
+            here = segment.end;
+            steps += n;
+        }
+        Path { segments }
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        self.segments.iter().map(|&(s, _)| s)
+    }
+
+    /// Total steps along the path
+    pub fn len(&self) -> i64 {
+        self.segments.last().map_or(0, |&(s, steps)| steps + s.length())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Steps along the path to the first visit of a point
+    pub fn steps_to(&self, p: Pos) -> Option<i64> {
+        self.segments
+            .iter()
+            .find_map(|&(s, before)| Some(before + s.steps_to(p)?))
+    }
+
+    /// Points on both paths, each with the steps along this path and along
+    /// `other` to its first visit. Compares every pair of segments rather
+    /// than visiting every point of either path.
+    pub fn crossings(&self, other: &Path) -> BTreeMap<Pos, (i64, i64)> {
+        let mut crossings: BTreeMap<Pos, (i64, i64)> = BTreeMap::new();
+        for &(a, before_a) in &self.segments {
+            for &(b, before_b) in &other.segments {
+                if let Some(shared) = a.intersection(b) {
+                    for p in shared.points() {
+                        let steps_a = before_a + a.steps_to(p).unwrap();
+                        let steps_b = before_b + b.steps_to(p).unwrap();
+                        let entry = crossings.entry(p).or_insert((steps_a, steps_b));
+                        *entry = (entry.0.min(steps_a), entry.1.min(steps_b));
+                    }
+                }
+            }
+        }
+        crossings
+    }
+}
+
+/// A rotation or reflection of the square grid followed by a translation.
+/// Transforms compose and invert without leaving the integer grid.
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Transform {
+    /// Images of the unit vectors east and south under the symmetry
+    x_axis: Pos,
+    y_axis: Pos,
+    offset: Pos,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        x_axis: Pos { x: 1, y: 0 },
+        y_axis: Pos { x: 0, y: 1 },
+        offset: Pos::ORIGIN,
+    };
+
+    /// Quarter turn clockwise about the origin, like `Pos::turn_clockwise`
+    pub const CLOCKWISE: Transform = Transform {
+        x_axis: Pos { x: 0, y: 1 },
+        y_axis: Pos { x: -1, y: 0 },
+        offset: Pos::ORIGIN,
+    };
+
+    /// Mirror image across the vertical axis
+    pub const FLIP_X: Transform = Transform {
+        x_axis: Pos { x: -1, y: 0 },
+        y_axis: Pos { x: 0, y: 1 },
+        offset: Pos::ORIGIN,
+    };
+
+    pub fn translate(offset: Pos) -> Transform {
+        Transform { offset, ..Transform::IDENTITY }
+    }
+
+    /// The four rotations, starting with the identity and turning
+    /// clockwise
+    pub fn rotations() -> [Transform; 4] {
+        let r1 = Transform::CLOCKWISE;
+        let r2 = r1.then(r1);
+        [Transform::IDENTITY, r1, r2, r2.then(r1)]
+    }
+
+    /// The eight rotations and reflections of the square
+    pub fn symmetries() -> [Transform; 8] {
+        let [r0, r1, r2, r3] = Transform::rotations();
+        let f = Transform::FLIP_X;
+        [r0, r1, r2, r3, f, f.then(r1), f.then(r2), f.then(r3)]
+    }
+
+    /// The transform that applies `self` and then `next`
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            x_axis: next.linear(self.x_axis),
+            y_axis: next.linear(self.y_axis),
+            offset: next.apply(self.offset),
+        }
+    }
+
+    pub fn inverse(self) -> Transform {
+        // the symmetry matrix is orthogonal, so its inverse is its transpose
+        let linear = Transform {
+            x_axis: Pos { x: self.x_axis.x, y: self.y_axis.x },
+            y_axis: Pos { x: self.x_axis.y, y: self.y_axis.y },
+            offset: Pos::ORIGIN,
+        };
+        Transform { offset: linear.apply(self.offset).turn_around(), ..linear }
+    }
+
+    /// Whether the transform mirrors the grid rather than only rotating it
+    pub fn is_reflection(self) -> bool {
+        self.x_axis.x * self.y_axis.y - self.x_axis.y * self.y_axis.x < 0
+    }
+
+    /// Image of a displacement, ignoring the translation
+    pub fn linear(self, p: Pos) -> Pos {
+        Pos {
+            x: self.x_axis.x * p.x + self.y_axis.x * p.y,
+            y: self.x_axis.y * p.x + self.y_axis.y * p.y,
+        }
+    }
+
+    pub fn apply(self, p: Pos) -> Pos {
+        self.linear(p) + self.offset
+    }
+
+    /// Image of a direction, which is unaffected by translation
+    pub fn apply_dir(self, dir: Dir) -> Dir {
+        let unit = self.linear(dir.unit());
+        Dir::iter().find(|d| d.unit() == unit).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    #[test]
+    fn turning() {
+        for d in Dir::iter() {
+            assert_eq!(d.turn_right(), Dir::CLOCKWISE[(d.index() + 1) % 4]);
+            assert_eq!(d.turn_left().turn_right(), d);
+            assert_eq!(Dir8::from(d).unit(), d.unit());
+            assert_eq!(Dir8::from(d.opposite()), Dir8::from(d).opposite());
+        }
+        assert_eq!(Dir8::NE.turn_right(), Dir8::E);
+        assert_eq!(Dir8::N.turn_left(), Dir8::NW);
+        assert_eq!(Dir8::SW.opposite(), Dir8::NE);
+    }
+
+    #[test]
+    fn text_round_trips() {
+        for &a in &Alphabet::ELEMS {
+            for &d in &Dir::ELEMS {
+                assert_eq!(Dir::parse_with(d.symbol(a), a), Ok(d));
+                assert_eq!(d.symbol(a).parse(), Ok(d));
+            }
+        }
+        assert_eq!(Dir::parse_with("N", Alphabet::Udlr), Err(ParseError("N".to_string())));
+        assert_eq!(Dir::W.to_string(), "W");
+        assert_eq!(Dir::parse_with(&Dir::E.code().to_string(), Alphabet::Code), Ok(Dir::E));
+
+        for turn in [Turn::Left, Turn::Right] {
+            assert_eq!(turn.to_string().parse(), Ok(turn));
+        }
+        assert_eq!(Dir::N.apply(Turn::Left), Dir::W);
+        assert_eq!(Turn::from_code(1).map(|t| Dir::W.apply(t)), Some(Dir::N));
+        assert_eq!(Turn::from_code(2), None);
+    }
+
+    #[test]
+    fn moore_neighborhood() {
+        let p = Pos { x: 3, y: 3 };
+        let ring: Vec<Pos> = Dir8::iter().map(|d| p.offset(d, 1)).collect();
+        assert_eq!(ring.len(), 8);
+        assert!(ring.iter().all(|&q| q != p && (q.x - p.x).abs() <= 1 && (q.y - p.y).abs() <= 1));
+        assert_eq!(p.offset(Dir8::SE, 2), Pos { x: 5, y: 5 });
+        assert_eq!(p.offset(Dir::W, 3), p.advance(Dir::W, 3));
+    }
+
+    #[test]
+    fn overflow() {
+        let edge = Pos { x: i64::MAX, y: 0 };
+        assert_eq!(edge.checked_advance(Dir::E, 1), None);
+        assert_eq!(edge.checked_advance(Dir::W, 1), Some(Pos { x: i64::MAX - 1, y: 0 }));
+        assert_eq!(Pos::ORIGIN.checked_advance(Dir::N, i64::MIN), None);
+        assert_eq!(edge.wrapping_advance(Dir::E, 1), Pos { x: i64::MIN, y: 0 });
+        assert_eq!(edge.checked_add(Pos { x: 0, y: 5 }), Some(Pos { x: i64::MAX, y: 5 }));
+        assert_eq!(Pos::ORIGIN.checked_sub(Pos { x: 0, y: i64::MIN }), None);
+        assert_eq!(edge.wrapping_add(edge).wrapping_sub(edge), edge);
+
+        assert_eq!(Pos::from_indices(3, 4), Ok(Pos { x: 3, y: 4 }));
+        assert!(Pos::from_indices(usize::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn rects() {
+        let p = |x, y| Pos { x, y };
+        let r = Rect::bounding(vec![p(3, 1), p(-1, 4), p(0, 0)]).unwrap();
+        assert_eq!(r, Rect::new(p(-1, 0), p(3, 4)));
+        assert_eq!((r.width(), r.height(), r.area()), (5, 5, 25));
+        assert_eq!(Rect::bounding(vec![]), None);
+        assert!(r.contains(p(3, 4)) && !r.contains(p(4, 4)));
+
+        let small = Rect::from_size(p(2, 3), 3, 2);
+        let cells: Vec<Pos> = small.positions().collect();
+        assert_eq!(cells, vec![p(2, 3), p(3, 3), p(4, 3), p(2, 4), p(3, 4), p(4, 4)]);
+        assert_eq!(r.intersection(small), Some(Rect::new(p(2, 3), p(3, 4))));
+        assert_eq!(small.intersection(Rect::from_size(p(5, 3), 1, 1)), None);
+        assert_eq!(small.expand(1), Rect::new(p(1, 2), p(5, 5)));
+        assert_eq!(small.including(p(0, 0)).area(), 25);
+    }
+
+    #[test]
+    fn segments() {
+        let p = |x, y| Pos { x, y };
+        let horizontal = Segment::new(p(5, 2), p(-1, 2));
+        let vertical = Segment::from_move(p(0, 0), Dir::S, 4);
+        assert_eq!(horizontal.length(), 6);
+        assert_eq!(horizontal.intersection(vertical), Some(Segment::new(p(0, 2), p(0, 2))));
+        assert_eq!(horizontal.steps_to(p(0, 2)), Some(5));
+        assert_eq!(vertical.steps_to(p(1, 2)), None);
+
+        let collinear = Segment::new(p(3, 2), p(9, 2));
+        let overlap = horizontal.intersection(collinear).unwrap();
+        assert_eq!(overlap, Segment::new(p(3, 2), p(5, 2)));
+        assert_eq!(overlap.points().collect::<Vec<_>>(), vec![p(3, 2), p(4, 2), p(5, 2)]);
+        assert_eq!(collinear.intersection(Segment::new(p(3, 3), p(9, 3))), None);
+    }
+
+    #[test]
+    fn paths_match_rasterized() {
+        let a = Path::new(Pos::ORIGIN, vec![(Dir::E, 8), (Dir::N, 5), (Dir::W, 5), (Dir::S, 3), (Dir::E, 6)]);
+        let b = Path::new(Pos::ORIGIN, vec![(Dir::N, 7), (Dir::E, 6), (Dir::S, 4), (Dir::W, 4)]);
+        assert_eq!(a.len(), 27);
+
+        let raster = |path: &Path| {
+            let mut first = BTreeMap::new();
+            let mut steps = 0;
+            for s in path.segments() {
+                for q in s.points().skip(1) {
+                    steps += 1;
+                    first.entry(q).or_insert(steps);
+                }
+            }
+            first.insert(Pos::ORIGIN, 0);
+            first
+        };
+        let (ra, rb) = (raster(&a), raster(&b));
+        let expected: BTreeMap<Pos, (i64, i64)> = ra
+            .iter()
+            .filter_map(|(q, &sa)| Some((*q, (sa, *rb.get(q)?))))
+            .collect();
+        assert_eq!(a.crossings(&b), expected);
+        for (q, &(sa, _)) in &expected {
+            assert_eq!(a.steps_to(*q), Some(sa));
+        }
+    }
+
+    #[test]
+    fn transforms() {
+        let p = Pos { x: 3, y: -5 };
+        let [r0, r1, r2, r3] = Transform::rotations();
+        assert_eq!(r0.apply(p), p);
+        assert_eq!(r1.apply(p), p.turn_clockwise());
+        assert_eq!(r2.apply(p), p.turn_around());
+        assert_eq!(r3.apply(p), p.turn_counterclockwise());
+        assert_eq!(r1.apply_dir(Dir::N), Dir::E);
+        assert_eq!(Transform::FLIP_X.apply_dir(Dir::E), Dir::W);
+
+        let symmetries = Transform::symmetries();
+        let images: std::collections::HashSet<Pos> = symmetries.iter().map(|t| t.apply(p)).collect();
+        assert_eq!(images.len(), 8);
+        assert_eq!(symmetries.iter().filter(|t| t.is_reflection()).count(), 4);
+
+        let shift = Transform::translate(Pos { x: 10, y: 1 });
+        for &t in &symmetries {
+            let t = t.then(shift);
+            assert_eq!(t.inverse().apply(t.apply(p)), p);
+            assert_eq!(t.then(t.inverse()), Transform::IDENTITY);
+            assert_eq!(t.then(r1).apply(p), r1.apply(t.apply(p)));
+            for d in Dir::iter() {
+                assert_eq!(t.apply(p.advance(d, 1)) - t.apply(p), t.apply_dir(d).unit());
+            }
+        }
+    }
+
+    fn asteroids(map: &str) -> Vec<Pos> {
+        let grid = Grid::from_str(map, |c| Some(c == '#')).unwrap();
+        grid.find(|&asteroid| asteroid).collect()
+    }
+
+    #[test]
+    fn bearings() {
+        let b = |x, y| Bearing::new(Pos { x, y }).unwrap();
+        assert_eq!(Bearing::new(Pos::ORIGIN), None);
+        assert_eq!(b(4, -6), b(2, -3));
+        assert_eq!(b(4, -6).step(), Pos { x: 2, y: -3 });
+        assert_ne!(b(2, -3), b(-2, 3));
+
+        let clockwise = [
+            b(0, -1),
+            b(1, -3),
+            b(1, -1),
+            b(3, -1),
+            b(1, 0),
+            b(2, 1),
+            b(0, 5),
+            b(-1, 1),
+            b(-1, 0),
+            b(-1, -7),
+        ];
+        let mut shuffled = clockwise;
+        shuffled.reverse();
+        shuffled.sort();
+        assert_eq!(shuffled, clockwise);
+        for d in Dir::iter() {
+            assert_eq!(b(d.unit().x, d.unit().y).step(), d.unit());
+        }
+    }
+
+    #[test]
+    fn extreme_bearings() {
+        let b = |x, y| Bearing::new(Pos { x, y }).unwrap();
+        assert_eq!(b(i64::MIN, 0), b(-1, 0));
+        assert_eq!(b(i64::MIN, i64::MIN), b(-1, -1));
+        assert_eq!(b(i64::MIN, i64::MAX).step(), Pos { x: i64::MIN, y: i64::MAX });
+
+        // these cross products overflow an i64
+        assert!(b(i64::MAX, -1) < b(1, 0));
+        assert!(b(1, 0) < b(i64::MAX, 1));
+        assert!(b(-1, i64::MAX) < b(i64::MIN, 1));
+    }
+
+    #[test]
+    fn monitoring_station() {
+        let small = asteroids(".#..#\n.....\n#####\n....#\n...##");
+        let best = small.iter().max_by_key(|&&p| visible(p, small.iter().copied())).unwrap();
+        assert_eq!(*best, Pos { x: 3, y: 4 });
+        assert_eq!(visible(*best, small.iter().copied()), 8);
+
+        let large = asteroids(LARGE);
+        let station = Pos { x: 11, y: 13 };
+        assert_eq!(visible(station, large.iter().copied()), 210);
+        let order = sweep(station, large.iter().copied());
+        assert_eq!(order.len(), large.len() - 1);
+        assert_eq!(order[0], Pos { x: 11, y: 12 });
+        assert_eq!(order[1], Pos { x: 12, y: 1 });
+        assert_eq!(order[19], Pos { x: 16, y: 0 });
+        assert_eq!(order[199], Pos { x: 8, y: 2 });
+        assert_eq!(order[298], Pos { x: 11, y: 1 });
+    }
+
+    const LARGE: &str = "\
+.#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##";
+}
@@ -0,0 +1,198 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::hash::Hash;
+use std::ops::Add;
+
+/// Queue entry ordered by priority, then insertion order, smallest first
+struct Entry<S, K> {
+    priority: K,
+    seq: u64,
+    cost: K,
+    state: S,
+}
+
+impl<S, K: Ord> PartialEq for Entry<S, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S, K: Ord> Eq for Entry<S, K> {}
+
+impl<S, K: Ord> PartialOrd for Entry<S, K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S, K: Ord> Ord for Entry<S, K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&other.priority, other.seq).cmp(&(&self.priority, self.seq))
+    }
+}
+
+/// Best-first search yielding each distinct state with the cost of the
+/// cheapest path to it. The heuristic must never overestimate the
+/// remaining cost and must be consistent for the yielded costs to be
+/// minimal.
+pub struct AStar<S, C, K, TFn, CFn, HFn> {
+    queue: BinaryHeap<Entry<S, K>>,
+    seq: u64,
+    visited: HashSet<C>,
+    transition: TFn,
+    characterize: CFn,
+    heuristic: HFn,
+}
+
+impl<S, C, K, I, TFn, CFn, HFn> AStar<S, C, K, TFn, CFn, HFn>
+    where
+        C: Eq + Hash,
+        K: Copy + Ord + Add<Output = K> + Default,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = (S, K)>,
+        CFn: FnMut(&S) -> C,
+        HFn: FnMut(&S) -> K,
+{
+    pub fn new(
+        initial: S,
+        transition: TFn,
+        characterize: CFn,
+        heuristic: HFn,
+    ) -> AStar<S, C, K, TFn, CFn, HFn>
+    {
+        let mut search = AStar {
+            queue: BinaryHeap::new(),
+            seq: 0,
+            visited: HashSet::new(),
+            transition,
+            characterize,
+            heuristic,
+        };
+        search.push(initial, K::default());
+        search
+    }
+
+    fn push(&mut self, state: S, cost: K) {
+        let priority = cost + (self.heuristic)(&state);
+        self.queue.push(Entry { priority, seq: self.seq, cost, state });
+        self.seq += 1;
+    }
+}
+
+impl<S, C, K, I, TFn, CFn, HFn> Iterator for AStar<S, C, K, TFn, CFn, HFn>
+    where
+        C: Eq + Hash,
+        K: Copy + Ord + Add<Output = K> + Default,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = (S, K)>,
+        CFn: FnMut(&S) -> C,
+        HFn: FnMut(&S) -> K,
+{
+    type Item = (S, K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Entry { cost, state, .. }) = self.queue.pop() {
+            if self.visited.insert((self.characterize)(&state)) {
+                for (next, step) in (self.transition)(&state) {
+                    self.push(next, cost + step);
+                }
+                return Some((state, cost));
+            }
+        }
+        None
+    }
+}
+
+type NoHeuristic<S, K> = fn(&S) -> K;
+
+/// Uniform-cost search: `AStar` without a heuristic
+pub struct Dijkstra<S, C, K, TFn, CFn>(AStar<S, C, K, TFn, CFn, NoHeuristic<S, K>>);
+
+impl<S, C, K, I, TFn, CFn> Dijkstra<S, C, K, TFn, CFn>
+    where
+        C: Eq + Hash,
+        K: Copy + Ord + Add<Output = K> + Default,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = (S, K)>,
+        CFn: FnMut(&S) -> C,
+{
+    pub fn new(
+        initial: S,
+        transition: TFn,
+        characterize: CFn,
+    ) -> Dijkstra<S, C, K, TFn, CFn>
+    {
+        Dijkstra(AStar::new(initial, transition, characterize, |_| K::default()))
+    }
+}
+
+impl<S, C, K, I, TFn, CFn> Iterator for Dijkstra<S, C, K, TFn, CFn>
+    where
+        C: Eq + Hash,
+        K: Copy + Ord + Add<Output = K> + Default,
+        TFn: FnMut(&S) -> I,
+        I: IntoIterator<Item = (S, K)>,
+        CFn: FnMut(&S) -> C,
+{
+    type Item = (S, K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pos::{Dir, Pos};
+    use std::collections::HashMap;
+
+    // Moving onto a cell costs its digit; '#' is a wall
+    const MAP: [&str; 4] = ["1163", "1#91", "2#11", "3111"];
+
+    fn cost(p: Pos) -> Option<u64> {
+        let row = MAP.get(p.y as usize)?.as_bytes();
+        let &c = row.get(p.x as usize)?;
+        if c == b'#' {
+            None
+        } else {
+            Some(u64::from(c - b'0'))
+        }
+    }
+
+    fn moves(&p: &Pos) -> Vec<(Pos, u64)> {
+        Dir::ELEMS
+            .iter()
+            .filter_map(|&d| {
+                let q = p.advance(d, 1);
+                cost(q).map(|c| (q, c))
+            })
+            .collect()
+    }
+
+    const GOAL: Pos = Pos { x: 3, y: 3 };
+
+    #[test]
+    fn dijkstra_costs() {
+        let found = Dijkstra::new(Pos::ORIGIN, moves, |&p| p).find(|&(p, _)| p == GOAL);
+        assert_eq!(found, Some((GOAL, 9)));
+    }
+
+    #[test]
+    fn astar_matches_dijkstra() {
+        let expected: HashMap<Pos, u64> = Dijkstra::new(Pos::ORIGIN, moves, |&p| p).collect();
+        let astar = AStar::new(Pos::ORIGIN, moves, |&p| p, |&p| (GOAL - p).norm1() as u64);
+        let mut visited = 0;
+        let mut goal_cost = None;
+        for (p, c) in astar {
+            visited += 1;
+            assert_eq!(c, expected[&p]);
+            if p == GOAL {
+                goal_cost = Some(c);
+                break;
+            }
+        }
+        assert_eq!(goal_cost, Some(expected[&GOAL]));
+        assert!(visited <= expected.len());
+    }
+}